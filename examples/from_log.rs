@@ -2,7 +2,7 @@ use std::error::Error;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
-type Result<T> = std::result::Result<T, Box<Error>>;
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
 
 use spell::map::LcsMap;
 
@@ -12,7 +12,7 @@ fn main() -> Result<()> {
     let max_lines: usize = std::env::args()
         .nth(2)
         .map(|i| i.parse().unwrap())
-        .unwrap_or_else(|| usize::max_value());
+        .unwrap_or(usize::MAX);
 
     let input = File::open(path)?;
     let buffered = BufReader::new(input);
@@ -36,7 +36,7 @@ fn main() -> Result<()> {
         .for_each(|(_i, _line)| {});
 
     println!("----------------------------");
-    println!("{}", map.to_string());
+    println!("{}", map);
 
     Ok(())
 }
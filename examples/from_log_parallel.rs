@@ -0,0 +1,39 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+use spell::map::LcsMap;
+
+fn main() -> Result<()> {
+    let path: String = std::env::args().nth(1).unwrap();
+
+    let max_lines: usize = std::env::args()
+        .nth(2)
+        .map(|i| i.parse().unwrap())
+        .unwrap_or(usize::MAX);
+
+    let input = File::open(path)?;
+    let buffered = BufReader::new(input);
+
+    let lines: Vec<String> = buffered
+        .lines()
+        .filter_map(|line| match line {
+            Ok(line) => Some(line),
+            Err(err) => {
+                eprintln!("Error processing line: {:?}", err);
+                None
+            }
+        })
+        .take(max_lines)
+        .collect();
+
+    let mut map = LcsMap::new();
+    map.insert_batch(&lines);
+
+    println!("----------------------------");
+    println!("{}", map);
+
+    Ok(())
+}
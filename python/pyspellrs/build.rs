@@ -3,9 +3,17 @@ extern crate cbindgen;
 use std::{env, path::Path};
 
 fn main() {
+    println!("cargo:rerun-if-changed=src/lib.rs");
+
     let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
-    let mut config: cbindgen::Config = Default::default();
+    let mut config = cbindgen::Config::default();
     config.language = cbindgen::Language::C;
+    config.include_guard = Some(String::from("SPELL_H"));
+    config.autogen_warning = Some(String::from(
+        "/* Generated by cbindgen from python/pyspellrs, do not edit. */",
+    ));
+    config.usize_is_size_t = true;
+    config.enumeration.rename_variants = cbindgen::RenameRule::QualifiedScreamingSnakeCase;
 
     let target_dir = env::var("CARGO_TARGET_DIR").unwrap_or(String::from("../../target"));
     let header_file = Path::new(&target_dir).join("spell.h");
@@ -1,6 +1,15 @@
-use std::{ffi::{CStr, CString}, os::raw::c_char};
+use std::{
+    cell::RefCell,
+    ffi::{CStr, CString},
+    os::raw::c_char,
+};
 
-use spell::{map::LcsMap, object::LcsObject, tokenize};
+use spell::{line_ids::LineIds, map::LcsMap, object::LcsObject};
+
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "arrow")]
+pub mod table;
 
 #[derive(Debug)]
 pub struct _Map {
@@ -9,7 +18,214 @@ pub struct _Map {
 
 #[derive(Debug)]
 pub struct _Object {
-    object: LcsObject,
+    /// Tokens of the template, resolved from the map's interner.
+    tokens: Vec<String>,
+    /// Whether each token is a wildcard, as a constant token may be `*` too.
+    wildcards: Vec<bool>,
+    lines_ids: LineIds,
+}
+
+/// Returned by every function. On anything but `SPELL_STATUS_OK`, the
+/// outputs are left untouched and `spell_last_error()` describes the error.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpellStatus {
+    Ok = 0,
+    /// A required pointer is null.
+    NullPointer,
+    /// A string is not valid UTF-8.
+    InvalidUtf8,
+    /// A delimiter is not a valid Unicode code point.
+    InvalidDelimiter,
+    /// An index is past the end of a template or map.
+    OutOfBounds,
+    /// No template matches the line.
+    NotFound,
+    /// The map could not be serialized.
+    Serialization,
+}
+
+/// Identifier given to the lines matching no template.
+pub const SPELL_NO_TEMPLATE: usize = !0;
+
+#[derive(Debug)]
+struct FfiError {
+    status: SpellStatus,
+    message: String,
+}
+
+impl FfiError {
+    fn new(status: SpellStatus, message: impl Into<String>) -> FfiError {
+        FfiError {
+            status,
+            message: message.into(),
+        }
+    }
+
+    fn null(name: &str) -> FfiError {
+        FfiError::new(
+            SpellStatus::NullPointer,
+            format!("Passed a null pointer for the {}", name),
+        )
+    }
+
+    fn out_of_bounds(i: u64, length: usize) -> FfiError {
+        FfiError::new(
+            SpellStatus::OutOfBounds,
+            format!("Index {} is out of bounds, length is {}", i, length),
+        )
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Run the body of an exported function, recording its error if any.
+fn run(f: impl FnOnce() -> Result<(), FfiError>) -> SpellStatus {
+    let (status, message) = match f() {
+        Ok(()) => (SpellStatus::Ok, None),
+        Err(e) => {
+            log::error!("{}", e.message);
+            // Interior nul bytes are the only reason `CString::new()` fails
+            let message = CString::new(e.message.replace('\0', "\\0")).unwrap();
+            (e.status, Some(message))
+        }
+    };
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = message);
+    status
+}
+
+unsafe fn map_ref<'a>(map_ptr: *const _Map) -> Result<&'a LcsMap, FfiError> {
+    map_ptr
+        .as_ref()
+        .map(|map| &map.map)
+        .ok_or_else(|| FfiError::null("map"))
+}
+
+unsafe fn map_mut<'a>(map_ptr: *mut _Map) -> Result<&'a mut LcsMap, FfiError> {
+    map_ptr
+        .as_mut()
+        .map(|map| &mut map.map)
+        .ok_or_else(|| FfiError::null("map"))
+}
+
+unsafe fn object_ref<'a>(object_ptr: *const _Object) -> Result<&'a _Object, FfiError> {
+    object_ptr.as_ref().ok_or_else(|| FfiError::null("object"))
+}
+
+unsafe fn to_str<'a>(ptr: *const c_char, name: &str) -> Result<&'a str, FfiError> {
+    if ptr.is_null() {
+        return Err(FfiError::null(name));
+    }
+    CStr::from_ptr(ptr).to_str().map_err(|e| {
+        FfiError::new(
+            SpellStatus::InvalidUtf8,
+            format!("Given {} is not a valid UTF-8 string: {}", name, e),
+        )
+    })
+}
+
+/// Write an output, checking its pointer.
+unsafe fn write<T>(out: *mut T, value: T) -> Result<(), FfiError> {
+    if out.is_null() {
+        return Err(FfiError::null("output"));
+    }
+    out.write(value);
+    Ok(())
+}
+
+/// Check an output array of `count` elements before doing any work. An empty
+/// array may be null.
+unsafe fn out_slice<'a, T>(out: *mut T, count: usize) -> Result<&'a mut [T], FfiError> {
+    if count == 0 {
+        return Ok(&mut []);
+    }
+    check_out(out)?;
+    Ok(std::slice::from_raw_parts_mut(out, count))
+}
+
+/// Lines of an array of `count` C strings, all checked before any is used. An
+/// empty array may be null.
+unsafe fn c_lines<'a>(lines: *const *const c_char, count: usize) -> Result<Vec<&'a str>, FfiError> {
+    if count == 0 {
+        return Ok(Vec::new());
+    } else if lines.is_null() {
+        return Err(FfiError::null("lines"));
+    }
+    std::slice::from_raw_parts(lines, count)
+        .iter()
+        .map(|&line| to_str(line, "line"))
+        .collect()
+}
+
+/// Lines of a buffer of `len` bytes, separated by `\n` or `\r\n`.
+unsafe fn buffer_lines<'a>(buffer: *const c_char, len: usize) -> Result<Vec<&'a str>, FfiError> {
+    if len == 0 {
+        return Ok(Vec::new());
+    } else if buffer.is_null() {
+        return Err(FfiError::null("buffer"));
+    }
+    let buffer = std::slice::from_raw_parts(buffer as *const u8, len);
+    let buffer = std::str::from_utf8(buffer).map_err(|e| {
+        FfiError::new(
+            SpellStatus::InvalidUtf8,
+            format!("Given buffer is not a valid UTF-8 string: {}", e),
+        )
+    })?;
+    Ok(buffer.lines().collect())
+}
+
+/// Hand an array of identifiers over to the caller, to be freed with
+/// `free_ids()`.
+unsafe fn write_ids(
+    ids: Vec<usize>,
+    out: *mut *mut usize,
+    out_len: *mut usize,
+) -> Result<(), FfiError> {
+    let len = ids.len();
+    write(out_len, len)?;
+    write(out, Box::into_raw(ids.into_boxed_slice()) as *mut usize)
+}
+
+/// Template matching the line, which goes through the map's log format,
+/// masking and tokenizer like an inserted line.
+fn match_line<'a>(map: &'a LcsMap, line: &str) -> Option<&'a LcsObject> {
+    map.classify(line).and_then(|id| map.get(id))
+}
+
+fn match_id(map: &LcsMap, line: &str) -> usize {
+    map.classify(line).unwrap_or(SPELL_NO_TEMPLATE)
+}
+
+/// Check the output pointer before doing any work.
+fn check_out<T>(out: *mut T) -> Result<(), FfiError> {
+    if out.is_null() {
+        Err(FfiError::null("output"))
+    } else {
+        Ok(())
+    }
+}
+
+fn new_object(map: &LcsMap, lcs_object: &LcsObject) -> *mut _Object {
+    Box::into_raw(Box::new(_Object {
+        tokens: lcs_object
+            .template(&map.interner)
+            .into_iter()
+            .map(|token| token.to_string())
+            .collect(),
+        wildcards: lcs_object.tokens.iter().map(|t| t.is_wildcard()).collect(),
+        lines_ids: lcs_object.lines_ids.clone(),
+    }))
+}
+
+fn new_string(string: &str) -> Result<*mut c_char, FfiError> {
+    CString::new(string).map(CString::into_raw).map_err(|e| {
+        FfiError::new(
+            SpellStatus::Serialization,
+            format!("String contains a nul byte: {}", e),
+        )
+    })
 }
 
 fn _init_env_logger() {
@@ -25,217 +241,718 @@ fn _init_env_logger() {
     let _ = env_logger::try_init();
 }
 
+/// Message of the error returned by the last function called on this
+/// thread, or null if it succeeded.
+///
+/// The message is owned by the library and valid until the next call on
+/// this thread. It must not be freed.
 #[no_mangle]
-pub unsafe extern "C" fn init_env_logger() {
-    _init_env_logger();
+pub extern "C" fn spell_last_error() -> *const c_char {
+    LAST_ERROR.with(|last_error| {
+        last_error
+            .borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |message| message.as_ptr())
+    })
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn new_map() -> *const _Map {
-    log::debug!("Creating new map...");
-    let map = LcsMap::new();
-
-    let map_ptr = Box::into_raw(Box::new(_Map { map })) as *const _Map;
-    log::debug!("Created map: {:?}", map_ptr);
-
-    map_ptr
+pub extern "C" fn init_env_logger() -> SpellStatus {
+    run(|| {
+        _init_env_logger();
+        Ok(())
+    })
 }
 
+/// Create a map splitting the lines on spaces, to be freed with `free_map()`.
+///
+/// # Safety
+///
+/// `out` must be null or valid for writes.
 #[no_mangle]
-pub unsafe extern "C" fn insert_in_map(map_ptr: *const _Map, line: *const c_char) {
-    log::debug!("Inserting line in map...");
-
-    if map_ptr.is_null() {
-        log::error!("ERROR: Passed a null pointer for the map");
-    } else if line.is_null() {
-        log::error!("ERROR: Passed a null pointer for the line");
-    } else {
-        let c_str = CStr::from_ptr(line);
+pub unsafe extern "C" fn new_map(out: *mut *mut _Map) -> SpellStatus {
+    run(|| {
+        log::debug!("Creating new map...");
+        let map_ptr = Box::into_raw(Box::new(_Map { map: LcsMap::new() }));
+        log::debug!("Created map: {:?}", map_ptr);
+        write(out, map_ptr)
+    })
+}
 
-        match c_str.to_str() {
-            Ok(line) => {
-                log::debug!("Inserting line into map: {:?}", line);
+/// Create a map splitting the lines on the given characters, passed as
+/// `len` Unicode code points.
+///
+/// # Safety
+///
+/// `delimiters` must be null or valid for reading `len` code points, and `out`
+/// must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn new_map_with_delimiters(
+    delimiters: *const u32,
+    len: usize,
+    out: *mut *mut _Map,
+) -> SpellStatus {
+    run(|| {
+        log::debug!("Creating new map with delimiters...");
+        check_out(out)?;
+        if delimiters.is_null() {
+            return Err(FfiError::null("delimiters"));
+        }
+        let delimiters = std::slice::from_raw_parts(delimiters, len)
+            .iter()
+            .map(|&code_point| {
+                std::char::from_u32(code_point).ok_or_else(|| {
+                    FfiError::new(
+                        SpellStatus::InvalidDelimiter,
+                        format!("Delimiter {:#x} is not a valid code point", code_point),
+                    )
+                })
+            })
+            .collect::<Result<Vec<char>, _>>()?;
+
+        let map_ptr = Box::into_raw(Box::new(_Map {
+            map: LcsMap::with_delimiters(delimiters),
+        }));
+        log::debug!("Created map: {:?}", map_ptr);
+        write(out, map_ptr)
+    })
+}
 
-                // We convert back to an `LcsMap` (from an `_Map` pointer) but we need to prevent
-                // it from being dropped when going out of scope.
-                let mut map = Box::from_raw(map_ptr as *mut _Map);
+/// # Safety
+///
+/// `map_ptr` must be null or a map from `new_map()` or
+/// `new_map_with_delimiters()`, not yet freed, and not used from another thread
+/// during the call. `line` must be null or a nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn insert_in_map(map_ptr: *mut _Map, line: *const c_char) -> SpellStatus {
+    run(|| {
+        let map = map_mut(map_ptr)?;
+        let line = to_str(line, "line")?;
+        log::debug!("Inserting line into map: {:?}", line);
+        map.insert(line);
+        Ok(())
+    })
+}
 
-                map.map.insert(line);
+/// Copy of the template matching the line, to be freed with `free_object()`.
+///
+/// Returns `SPELL_STATUS_NOT_FOUND` if no template matches.
+///
+/// # Safety
+///
+/// `map_ptr` must be null or a map from `new_map()` or
+/// `new_map_with_delimiters()`, not yet freed. `line` must be null or a nul-
+/// terminated string, and `out` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn get_match(
+    map_ptr: *const _Map,
+    line: *const c_char,
+    out: *mut *mut _Object,
+) -> SpellStatus {
+    run(|| {
+        let map = map_ref(map_ptr)?;
+        let line = to_str(line, "line")?;
+        check_out(out)?;
+        log::debug!("Matching line into map: {:?}", line);
+
+        let lcs_object = match_line(map, line).ok_or_else(|| {
+            FfiError::new(
+                SpellStatus::NotFound,
+                format!("Line not found in map: {:?}", line),
+            )
+        })?;
+        log::info!("Line found in map: {:?}", lcs_object);
+        write(out, new_object(map, lcs_object))
+    })
+}
 
-                // Don't drop the map!
-                std::mem::forget(map);
-            }
-            Err(e) => {
-                log::error!("Given line is not a valid UTF-8 string {:?}", e);
-            }
+/// Insert `count` lines, writing the identifier of the template of each line
+/// to `out_ids`, an array of `count` elements. Both arrays may be null when
+/// `count` is 0.
+///
+/// Nothing is inserted if any line is invalid.
+///
+/// # Safety
+///
+/// `map_ptr` must be null or a map from `new_map()` or
+/// `new_map_with_delimiters()`, not yet freed, and not used from another thread
+/// during the call. `lines` must be null or point to `count` nul-terminated
+/// strings, and `out_ids` must be null or valid for writing `count`
+/// identifiers.
+#[no_mangle]
+pub unsafe extern "C" fn insert_many(
+    map_ptr: *mut _Map,
+    lines: *const *const c_char,
+    count: usize,
+    out_ids: *mut usize,
+) -> SpellStatus {
+    run(|| {
+        let map = map_mut(map_ptr)?;
+        let lines = c_lines(lines, count)?;
+        let out_ids = out_slice(out_ids, count)?;
+        for (line, out_id) in lines.into_iter().zip(out_ids) {
+            *out_id = map.insert_returning_id(line);
         }
-    }
+        Ok(())
+    })
 }
 
+/// Write the identifier of the template matching each of the `count` lines
+/// to `out_ids`, an array of `count` elements. Lines matching no template
+/// get `SPELL_NO_TEMPLATE`. Both arrays may be null when `count` is 0.
+///
+/// # Safety
+///
+/// `map_ptr` must be null or a map from `new_map()` or
+/// `new_map_with_delimiters()`, not yet freed. `lines` must be null or point to
+/// `count` nul-terminated strings, and `out_ids` must be null or valid for
+/// writing `count` identifiers.
 #[no_mangle]
-pub unsafe extern "C" fn get_match(map_ptr: *const _Map, line: *const c_char) -> *const _Object {
-    log::debug!("Matching line in map...");
-
-    if map_ptr.is_null() {
-        log::error!("ERROR: Passed a null pointer for the map");
-        std::ptr::null()
-    } else if line.is_null() {
-        log::error!("ERROR: Passed a null pointer for the line");
-        std::ptr::null()
-    } else {
-        let c_str = CStr::from_ptr(line);
-
-        match c_str.to_str() {
-            Ok(line) => {
-                log::debug!("Matching line into map: {:?}", line);
-
-                // We convert back to an `LcsMap` (from an `_Map` pointer) but we need to prevent
-                // it from being dropped when going out of scope.
-                let map = Box::from_raw(map_ptr as *mut _Map);
-
-                let tokens = tokenize(&line, map.map.delimiters.as_slice())
-                    .map(|token| token.to_string())
-                    .collect();
-
-                let object_ptr = match map.map.get_match(&tokens) {
-                    Some(lcs_objects) => {
-                        log::info!("Line found in map: {:?}", lcs_objects);
-
-                        Box::into_raw(Box::new(_Object {
-                            object: LcsObject {
-                                tokens: lcs_objects.tokens.clone(),
-                                lines_ids: lcs_objects.lines_ids.clone(),
-                            },
-                        })) as *const _Object
-                    }
-                    None => {
-                        log::warn!("Line not found in map");
-                        std::ptr::null()
-                    }
-                };
-
-                // Don't drop the map!
-                std::mem::forget(map);
-
-                object_ptr
-            }
-            Err(e) => {
-                log::error!("Given line is not a valid UTF-8 string {:?}", e);
-                std::ptr::null()
-            }
+pub unsafe extern "C" fn match_many(
+    map_ptr: *const _Map,
+    lines: *const *const c_char,
+    count: usize,
+    out_ids: *mut usize,
+) -> SpellStatus {
+    run(|| {
+        let map = map_ref(map_ptr)?;
+        let lines = c_lines(lines, count)?;
+        let out_ids = out_slice(out_ids, count)?;
+        for (line, out_id) in lines.into_iter().zip(out_ids) {
+            *out_id = match_id(map, line);
         }
-    }
+        Ok(())
+    })
 }
 
+/// Same as `insert_many()`, for the lines of a buffer of `len` bytes. The
+/// identifiers are returned in an array of `out_len` elements, to be freed
+/// with `free_ids()`.
+///
+/// # Safety
+///
+/// `map_ptr` must be null or a map from `new_map()` or
+/// `new_map_with_delimiters()`, not yet freed, and not used from another thread
+/// during the call. `buffer` must be null or valid for reading `len` bytes, and
+/// `out_ids` and `out_len` must be null or valid for writes.
 #[no_mangle]
-pub unsafe extern "C" fn free_map(map_ptr: *const _Map) {
-    log::debug!("Attempting to drop map {:?}", map_ptr);
-    if map_ptr.is_null() {
-        log::warn!("Attempted to drop null pointer. Skipping.");
-    } else {
-        log::debug!("Dropping map...");
-        let map = Box::from_raw(map_ptr as *mut _Map);
-        std::mem::drop(map);
-    }
+pub unsafe extern "C" fn insert_buffer(
+    map_ptr: *mut _Map,
+    buffer: *const c_char,
+    len: usize,
+    out_ids: *mut *mut usize,
+    out_len: *mut usize,
+) -> SpellStatus {
+    run(|| {
+        let map = map_mut(map_ptr)?;
+        let lines = buffer_lines(buffer, len)?;
+        check_out(out_ids)?;
+        check_out(out_len)?;
+        let ids = lines
+            .into_iter()
+            .map(|line| map.insert_returning_id(line))
+            .collect();
+        write_ids(ids, out_ids, out_len)
+    })
 }
 
+/// Same as `match_many()`, for the lines of a buffer of `len` bytes. The
+/// identifiers are returned in an array of `out_len` elements, to be freed
+/// with `free_ids()`.
+///
+/// # Safety
+///
+/// `map_ptr` must be null or a map from `new_map()` or
+/// `new_map_with_delimiters()`, not yet freed. `buffer` must be null or valid
+/// for reading `len` bytes, and `out_ids` and `out_len` must be null or valid
+/// for writes.
 #[no_mangle]
-pub unsafe extern "C" fn object_tokens_len(object_ptr: *const _Object) -> u64 {
-    if object_ptr.is_null() {
-        0
-    } else {
-        let object = Box::from_raw(object_ptr as *mut _Object);
-
-        let length = object.object.tokens.len();
-
-        std::mem::forget(object);
-
-        length as u64
-    }
+pub unsafe extern "C" fn match_buffer(
+    map_ptr: *const _Map,
+    buffer: *const c_char,
+    len: usize,
+    out_ids: *mut *mut usize,
+    out_len: *mut usize,
+) -> SpellStatus {
+    run(|| {
+        let map = map_ref(map_ptr)?;
+        let lines = buffer_lines(buffer, len)?;
+        check_out(out_ids)?;
+        check_out(out_len)?;
+        let ids = lines.into_iter().map(|line| match_id(map, line)).collect();
+        write_ids(ids, out_ids, out_len)
+    })
 }
 
+/// Number of templates in the map.
+///
+/// # Safety
+///
+/// `map_ptr` must be null or a map from `new_map()` or
+/// `new_map_with_delimiters()`, not yet freed, and `out` must be null or valid
+/// for writes.
 #[no_mangle]
-pub unsafe extern "C" fn object_lines_ids_len(object_ptr: *const _Object) -> u64 {
-    if object_ptr.is_null() {
-        0
-    } else {
-        let object = Box::from_raw(object_ptr as *mut _Object);
-
-        let length = object.object.lines_ids.len();
-
-        std::mem::forget(object);
+pub unsafe extern "C" fn map_len(map_ptr: *const _Map, out: *mut u64) -> SpellStatus {
+    run(|| write(out, map_ref(map_ptr)?.seq.len() as u64))
+}
 
-        length as u64
-    }
+/// Copy of the `i`-th template of the map, to be freed with `free_object()`.
+///
+/// # Safety
+///
+/// `map_ptr` must be null or a map from `new_map()` or
+/// `new_map_with_delimiters()`, not yet freed, and `out` must be null or valid
+/// for writes.
+#[no_mangle]
+pub unsafe extern "C" fn map_ith_object(
+    map_ptr: *const _Map,
+    i: u64,
+    out: *mut *mut _Object,
+) -> SpellStatus {
+    run(|| {
+        let map = map_ref(map_ptr)?;
+        check_out(out)?;
+        let lcs_object = map
+            .seq
+            .get(i as usize)
+            .ok_or_else(|| FfiError::out_of_bounds(i, map.seq.len()))?;
+        write(out, new_object(map, lcs_object))
+    })
 }
 
+/// Number of lines inserted in the map.
+///
+/// # Safety
+///
+/// `map_ptr` must be null or a map from `new_map()` or
+/// `new_map_with_delimiters()`, not yet freed, and `out` must be null or valid
+/// for writes.
 #[no_mangle]
-pub unsafe extern "C" fn object_ith_token(object_ptr: *const _Object, i: u64) -> *const c_char {
-    if object_ptr.is_null() {
-        std::ptr::null()
-    } else {
-        let object = Box::from_raw(object_ptr as *mut _Object);
+pub unsafe extern "C" fn map_line_count(map_ptr: *const _Map, out: *mut u64) -> SpellStatus {
+    run(|| write(out, map_ref(map_ptr)?.line_id as u64))
+}
 
-        let i = i as usize;
-        let token_str_ptr = if i < object.object.tokens.len() {
-            let c_str = CString::new(object.object.tokens[i].clone()).unwrap();
-            c_str.into_raw()
-        } else {
-            std::ptr::null()
-        };
+/// Whole map, serialized in JSON, to be freed with `free_string()`.
+///
+/// # Safety
+///
+/// `map_ptr` must be null or a map from `new_map()` or
+/// `new_map_with_delimiters()`, not yet freed, and `out` must be null or valid
+/// for writes.
+#[no_mangle]
+pub unsafe extern "C" fn map_to_json(map_ptr: *const _Map, out: *mut *mut c_char) -> SpellStatus {
+    run(|| {
+        let map = map_ref(map_ptr)?;
+        check_out(out)?;
+        let json = serde_json::to_string(map).map_err(|e| {
+            FfiError::new(
+                SpellStatus::Serialization,
+                format!("Failed to serialize map: {}", e),
+            )
+        })?;
+        write(out, new_string(&json)?)
+    })
+}
 
-        std::mem::forget(object);
+/// Free a map. Null pointers are ignored.
+///
+/// # Safety
+///
+/// `map_ptr` must be null or a map from `new_map()` or
+/// `new_map_with_delimiters()`, not yet freed. The map must not be used
+/// afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn free_map(map_ptr: *mut _Map) -> SpellStatus {
+    run(|| {
+        log::debug!("Dropping map {:?}", map_ptr);
+        if !map_ptr.is_null() {
+            drop(Box::from_raw(map_ptr));
+        }
+        Ok(())
+    })
+}
 
-        token_str_ptr
-    }
+/// # Safety
+///
+/// `object_ptr` must be null or an object from `get_match()` or
+/// `map_ith_object()`, not yet freed, and `out` must be null or valid for
+/// writes.
+#[no_mangle]
+pub unsafe extern "C" fn object_tokens_len(
+    object_ptr: *const _Object,
+    out: *mut u64,
+) -> SpellStatus {
+    run(|| write(out, object_ref(object_ptr)?.tokens.len() as u64))
 }
 
+/// # Safety
+///
+/// `object_ptr` must be null or an object from `get_match()` or
+/// `map_ith_object()`, not yet freed, and `out` must be null or valid for
+/// writes.
 #[no_mangle]
-pub unsafe extern "C" fn object_ith_line_id(object_ptr: *const _Object, i: u64) -> usize {
-    if object_ptr.is_null() {
-        log::error!("Null pointer passed to object_ith_line_id(), returning 0 instead of crashing.");
-        0
-    } else {
-        let object = Box::from_raw(object_ptr as *mut _Object);
+pub unsafe extern "C" fn object_lines_ids_len(
+    object_ptr: *const _Object,
+    out: *mut u64,
+) -> SpellStatus {
+    run(|| write(out, object_ref(object_ptr)?.lines_ids.len() as u64))
+}
 
-        let i = i as usize;
-        let line_id_str_ptr = if i < object.object.lines_ids.len() {
-            object.object.lines_ids[i]
-        } else {
-            log::error!("Index passed to object_ith_line_id() larger than length, returning 0 instead of crashing.");
-            0
-        };
+/// Copy of the `i`-th token of the template, to be freed with `free_string()`.
+/// Wildcards are `*`: `object_ith_token_is_wildcard()` tells them apart.
+///
+/// # Safety
+///
+/// `object_ptr` must be null or an object from `get_match()` or
+/// `map_ith_object()`, not yet freed, and `out` must be null or valid for
+/// writes.
+#[no_mangle]
+pub unsafe extern "C" fn object_ith_token(
+    object_ptr: *const _Object,
+    i: u64,
+    out: *mut *mut c_char,
+) -> SpellStatus {
+    run(|| {
+        let object = object_ref(object_ptr)?;
+        check_out(out)?;
+        let token = object
+            .tokens
+            .get(i as usize)
+            .ok_or_else(|| FfiError::out_of_bounds(i, object.tokens.len()))?;
+        write(out, new_string(token)?)
+    })
+}
 
-        std::mem::forget(object);
+/// Whether the `i`-th token of the template is a wildcard, rather than a
+/// constant token.
+///
+/// # Safety
+///
+/// `object_ptr` must be null or an object from `get_match()` or
+/// `map_ith_object()`, not yet freed, and `out` must be null or valid for
+/// writes.
+#[no_mangle]
+pub unsafe extern "C" fn object_ith_token_is_wildcard(
+    object_ptr: *const _Object,
+    i: u64,
+    out: *mut bool,
+) -> SpellStatus {
+    run(|| {
+        let object = object_ref(object_ptr)?;
+        let is_wildcard = object
+            .wildcards
+            .get(i as usize)
+            .ok_or_else(|| FfiError::out_of_bounds(i, object.wildcards.len()))?;
+        write(out, *is_wildcard)
+    })
+}
 
-        line_id_str_ptr
-    }
+/// # Safety
+///
+/// `object_ptr` must be null or an object from `get_match()` or
+/// `map_ith_object()`, not yet freed, and `out` must be null or valid for
+/// writes.
+#[no_mangle]
+pub unsafe extern "C" fn object_ith_line_id(
+    object_ptr: *const _Object,
+    i: u64,
+    out: *mut usize,
+) -> SpellStatus {
+    run(|| {
+        let object = object_ref(object_ptr)?;
+        let line_id = object
+            .lines_ids
+            .get(i as usize)
+            .ok_or_else(|| FfiError::out_of_bounds(i, object.lines_ids.len()))?;
+        write(out, line_id)
+    })
 }
 
+/// Free a template. Null pointers are ignored.
+///
+/// # Safety
+///
+/// `object_ptr` must be null or an object from `get_match()` or
+/// `map_ith_object()`, not yet freed. The object must not be used afterwards.
 #[no_mangle]
-pub unsafe extern "C" fn free_object(object_ptr: *const _Object) {
-    log::debug!("Attempting to drop object {:?}", object_ptr);
-    if object_ptr.is_null() {
-        log::warn!("Attempted to drop null pointer. Skipping.");
-    } else {
-        log::debug!("Dropping object...");
-        let object = Box::from_raw(object_ptr as *mut _Object);
-        std::mem::drop(object);
-    }
+pub unsafe extern "C" fn free_object(object_ptr: *mut _Object) -> SpellStatus {
+    run(|| {
+        log::debug!("Dropping object {:?}", object_ptr);
+        if !object_ptr.is_null() {
+            drop(Box::from_raw(object_ptr));
+        }
+        Ok(())
+    })
 }
 
-// #[no_mangle]
-// pub unsafe extern "C" fn with_delimiters() -> *const _Map {
-//     // delimiters: Vec<char>
-//     // let map = LcsMap::with_delimiters(vec![' ', ',']);
+/// Free an array of `len` identifiers returned by this library. Null
+/// pointers are ignored.
+///
+/// # Safety
+///
+/// `ids` must be null or an array returned by `insert_buffer()` or
+/// `match_buffer()`, with the length returned along with it, not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn free_ids(ids: *mut usize, len: usize) -> SpellStatus {
+    run(|| {
+        if !ids.is_null() {
+            drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(ids, len)));
+        }
+        Ok(())
+    })
+}
 
-//     Box::into_raw(Box::new(_Map { map })) as *const _Map
-// }
+/// Free a string returned by this library. Null pointers are ignored.
+///
+/// # Safety
+///
+/// `string` must be null or a string returned by this library, not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn free_string(string: *mut c_char) -> SpellStatus {
+    run(|| {
+        if !string.is_null() {
+            drop(CString::from_raw(string));
+        }
+        Ok(())
+    })
+}
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use spell::log_format::LogFormat;
+
     #[test]
     fn it_works() {
         assert_eq!(2 + 2, 4);
     }
+
+    /// Value written by a function returning `SPELL_STATUS_OK`.
+    unsafe fn output<T>(f: impl FnOnce(*mut T) -> SpellStatus) -> T {
+        let mut out = std::mem::MaybeUninit::uninit();
+        assert_eq!(f(out.as_mut_ptr()), SpellStatus::Ok);
+        out.assume_init()
+    }
+
+    unsafe fn take_string(string: *mut c_char) -> String {
+        let owned = CStr::from_ptr(string).to_str().unwrap().to_string();
+        assert_eq!(free_string(string), SpellStatus::Ok);
+        owned
+    }
+
+    #[test]
+    fn introspection() {
+        let delimiters = [' ' as u32, ',' as u32];
+        unsafe {
+            let map =
+                output(|out| new_map_with_delimiters(delimiters.as_ptr(), delimiters.len(), out));
+            for line in &[
+                "Command Failed on: node-127,node-234",
+                "Command Failed on: node-128,node-234",
+                "Temperature (41C) exceeds warning threshold",
+            ] {
+                let line = CString::new(*line).unwrap();
+                assert_eq!(insert_in_map(map, line.as_ptr()), SpellStatus::Ok);
+            }
+            assert_eq!(output(|out| map_len(map, out)), 2);
+            assert_eq!(output(|out| map_line_count(map, out)), 3);
+
+            let object = output(|out| map_ith_object(map, 0, out));
+            assert_eq!(output(|out| object_tokens_len(object, out)), 5);
+            let token = output(|out| object_ith_token(object, 3, out));
+            assert_eq!(take_string(token), "*");
+            assert!(output(|out| object_ith_token_is_wildcard(object, 3, out)));
+            assert!(!output(|out| object_ith_token_is_wildcard(object, 2, out)));
+            assert_eq!(output(|out| object_lines_ids_len(object, out)), 2);
+            assert_eq!(output(|out| object_ith_line_id(object, 1, out)), 1);
+            assert_eq!(free_object(object), SpellStatus::Ok);
+
+            let json = take_string(output(|out| map_to_json(map, out)));
+            let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+            assert_eq!(json["line_id"], 3);
+            assert_eq!(json["seq"].as_array().unwrap().len(), 2);
+
+            let line = CString::new("Command Failed on: node-129,node-234").unwrap();
+            let object = output(|out| get_match(map, line.as_ptr(), out));
+            assert_eq!(output(|out| object_lines_ids_len(object, out)), 2);
+            assert_eq!(free_object(object), SpellStatus::Ok);
+            assert_eq!(free_map(map), SpellStatus::Ok);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        unsafe {
+            let map = output(|out| new_map(out));
+            assert!(spell_last_error().is_null());
+            let last_error = || CStr::from_ptr(spell_last_error()).to_str().unwrap();
+
+            let mut object = std::ptr::null_mut();
+            let line = CString::new("Temperature (41C) exceeds warning threshold").unwrap();
+            assert_eq!(
+                get_match(map, line.as_ptr(), &mut object),
+                SpellStatus::NotFound
+            );
+            assert!(object.is_null());
+            assert!(last_error().starts_with("Line not found in map"));
+
+            assert_eq!(
+                insert_in_map(std::ptr::null_mut(), line.as_ptr()),
+                SpellStatus::NullPointer
+            );
+            assert_eq!(last_error(), "Passed a null pointer for the map");
+            assert_eq!(insert_in_map(map, line.as_ptr()), SpellStatus::Ok);
+            assert!(spell_last_error().is_null());
+
+            let invalid = [0xff, 0];
+            assert_eq!(
+                insert_in_map(map, invalid.as_ptr() as *const c_char),
+                SpellStatus::InvalidUtf8
+            );
+            let mut length = 0;
+            assert_eq!(map_len(map, std::ptr::null_mut()), SpellStatus::NullPointer);
+            assert_eq!(map_len(map, &mut length), SpellStatus::Ok);
+            assert_eq!(length, 1);
+
+            let mut object = std::ptr::null_mut();
+            assert_eq!(
+                map_ith_object(map, 1, &mut object),
+                SpellStatus::OutOfBounds
+            );
+            assert_eq!(last_error(), "Index 1 is out of bounds, length is 1");
+
+            let mut new = std::ptr::null_mut();
+            let delimiters = [0xD800];
+            assert_eq!(
+                new_map_with_delimiters(delimiters.as_ptr(), delimiters.len(), &mut new),
+                SpellStatus::InvalidDelimiter
+            );
+            assert!(new.is_null());
+
+            assert_eq!(free_map(map), SpellStatus::Ok);
+            assert_eq!(free_map(std::ptr::null_mut()), SpellStatus::Ok);
+            assert_eq!(free_string(std::ptr::null_mut()), SpellStatus::Ok);
+        }
+    }
+
+    #[test]
+    fn batches() {
+        unsafe {
+            let map = output(|out| new_map(out));
+            let lines: Vec<CString> = [
+                "Command Failed on: node-127",
+                "Temperature (41C) exceeds warning threshold",
+                "Command Failed on: node-128",
+            ]
+            .iter()
+            .map(|line| CString::new(*line).unwrap())
+            .collect();
+            let pointers: Vec<*const c_char> = lines.iter().map(|line| line.as_ptr()).collect();
+            let mut ids = [42; 3];
+            assert_eq!(
+                insert_many(map, pointers.as_ptr(), pointers.len(), ids.as_mut_ptr()),
+                SpellStatus::Ok
+            );
+            assert_eq!(ids, [0, 1, 0]);
+
+            let buffer = "Temperature (43C) exceeds warning threshold\r\nCommand Failed on: node-129\nUnknown\n";
+            let mut ids = std::ptr::null_mut();
+            let len = output(|out_len| {
+                match_buffer(
+                    map,
+                    buffer.as_ptr() as *const c_char,
+                    buffer.len(),
+                    &mut ids,
+                    out_len,
+                )
+            });
+            assert_eq!(
+                std::slice::from_raw_parts(ids, len),
+                [1, 0, SPELL_NO_TEMPLATE]
+            );
+            assert_eq!(free_ids(ids, len), SpellStatus::Ok);
+
+            let len = output(|out_len| {
+                insert_buffer(
+                    map,
+                    buffer.as_ptr() as *const c_char,
+                    buffer.len(),
+                    &mut ids,
+                    out_len,
+                )
+            });
+            assert_eq!(std::slice::from_raw_parts(ids, len), [1, 0, 2]);
+            assert_eq!(free_ids(ids, len), SpellStatus::Ok);
+
+            let mut ids = [42; 4];
+            assert_eq!(
+                match_many(map, pointers.as_ptr(), pointers.len(), ids.as_mut_ptr()),
+                SpellStatus::Ok
+            );
+            assert_eq!(ids, [0, 1, 0, 42]);
+
+            // Empty batches may come without arrays
+            let null = std::ptr::null_mut();
+            assert_eq!(insert_many(map, std::ptr::null(), 0, null), SpellStatus::Ok);
+            assert_eq!(match_many(map, std::ptr::null(), 0, null), SpellStatus::Ok);
+            let mut empty = std::ptr::null_mut();
+            let len =
+                output(|out_len| insert_buffer(map, std::ptr::null(), 0, &mut empty, out_len));
+            assert_eq!(len, 0);
+            assert_eq!(free_ids(empty, len), SpellStatus::Ok);
+            let len = output(|out_len| match_buffer(map, std::ptr::null(), 0, &mut empty, out_len));
+            assert_eq!(len, 0);
+            assert_eq!(free_ids(empty, len), SpellStatus::Ok);
+
+            // Nothing is inserted when a line is invalid
+            let invalid = [0xff, 0];
+            let pointers = [pointers[0], invalid.as_ptr() as *const c_char];
+            assert_eq!(
+                insert_many(map, pointers.as_ptr(), pointers.len(), ids.as_mut_ptr()),
+                SpellStatus::InvalidUtf8
+            );
+            assert_eq!(output(|out| map_line_count(map, out)), 6);
+            assert_eq!(free_map(map), SpellStatus::Ok);
+        }
+    }
+
+    #[test]
+    fn match_like_insert() {
+        let log_format = LogFormat::new("<Date> <Time> <Host> <Component>: <Content>").unwrap();
+        let mut map = LcsMap::with_log_format(log_format);
+        map.insert("2024-01-02 10:00:00 host-1 app: Server started");
+        let map = Box::into_raw(Box::new(_Map { map }));
+
+        // Only the content is matched, the headers would be too different
+        let line = CString::new("2024-01-03 11:30:00 host-2 db: Server started").unwrap();
+        unsafe {
+            let object = output(|out| get_match(map, line.as_ptr(), out));
+            assert_eq!(output(|out| object_tokens_len(object, out)), 2);
+            assert_eq!(free_object(object), SpellStatus::Ok);
+
+            let pointers = [line.as_ptr()];
+            let mut ids = [42];
+            assert_eq!(
+                match_many(map, pointers.as_ptr(), pointers.len(), ids.as_mut_ptr()),
+                SpellStatus::Ok
+            );
+            assert_eq!(ids, [0]);
+
+            let buffer = "2024-01-03 11:30:00 host-2 db: Server started\nUnknown\n";
+            let mut ids = std::ptr::null_mut();
+            let len = output(|out_len| {
+                match_buffer(
+                    map,
+                    buffer.as_ptr() as *const c_char,
+                    buffer.len(),
+                    &mut ids,
+                    out_len,
+                )
+            });
+            assert_eq!(std::slice::from_raw_parts(ids, len), [0, SPELL_NO_TEMPLATE]);
+            assert_eq!(free_ids(ids, len), SpellStatus::Ok);
+            assert_eq!(output(|out| map_line_count(map, out)), 1);
+            assert_eq!(free_map(map), SpellStatus::Ok);
+        }
+    }
 }
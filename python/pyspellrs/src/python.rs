@@ -0,0 +1,208 @@
+//! Python extension module, exposing `Spell` and its templates.
+
+use std::path::PathBuf;
+
+use arrow::{pyarrow::ToPyArrow, record_batch::RecordBatch};
+use pyo3::{
+    exceptions::{PyKeyError, PyValueError},
+    prelude::*,
+    types::PyBytes,
+};
+
+use spell::{
+    config::SpellConfig, error::Error, map::LcsMap, object::LcsObject, persistence::Format, LineId,
+    TemplateId,
+};
+
+use crate::table;
+
+fn to_py_err(e: Error) -> PyErr {
+    PyValueError::new_err(e.to_string())
+}
+
+/// Lines passed at once, either as a sequence of strings or as a single
+/// string of newline separated lines.
+#[derive(FromPyObject)]
+enum Lines {
+    Buffer(String),
+    Lines(Vec<String>),
+}
+
+impl Lines {
+    fn iter(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        match self {
+            Lines::Buffer(buffer) => Box::new(buffer.lines()),
+            Lines::Lines(lines) => Box::new(lines.iter().map(String::as_str)),
+        }
+    }
+}
+
+/// Templates learned from the lines inserted.
+#[pyclass(module = "spell")]
+pub struct Spell {
+    map: LcsMap,
+}
+
+#[pymethods]
+impl Spell {
+    #[new]
+    #[pyo3(signature = (delimiters = None, tau = None))]
+    fn new(delimiters: Option<Vec<char>>, tau: Option<f64>) -> Spell {
+        let mut map = LcsMap::with_delimiters(delimiters.unwrap_or_else(|| vec![' ']));
+        if let Some(tau) = tau {
+            map.config = SpellConfig::default().with_tau(tau);
+        }
+        Spell { map }
+    }
+
+    /// Insert a line, returning the identifier of its template.
+    fn insert(&mut self, line: &str) -> TemplateId {
+        self.map.insert_returning_id(line)
+    }
+
+    /// Insert many lines, given as a list or as a string of lines, returning
+    /// the identifier of the template of each line.
+    ///
+    /// The GIL is released during the insertion: other threads using the map
+    /// meanwhile get a `RuntimeError`.
+    fn insert_many(&mut self, py: Python<'_>, lines: Lines) -> Vec<TemplateId> {
+        let map = &mut self.map;
+        py.allow_threads(|| {
+            lines
+                .iter()
+                .map(|line| map.insert_returning_id(line))
+                .collect()
+        })
+    }
+
+    /// Identifier of the template matching each line, or `None`, without
+    /// inserting the lines.
+    fn match_many(&self, py: Python<'_>, lines: Lines) -> Vec<Option<TemplateId>> {
+        let map = &self.map;
+        py.allow_threads(|| lines.iter().map(|line| map.classify(line)).collect())
+    }
+
+    /// Template matching the line, without inserting it.
+    #[pyo3(name = "match")]
+    fn match_line(slf: &Bound<'_, Spell>, line: &str) -> PyResult<Option<Template>> {
+        let id = slf.try_borrow()?.map.classify(line);
+        Ok(id.map(|id| Template {
+            spell: slf.clone().unbind(),
+            id,
+        }))
+    }
+
+    /// All the templates, by increasing identifier.
+    fn templates(slf: &Bound<'_, Spell>) -> PyResult<Vec<Template>> {
+        Ok(slf
+            .try_borrow()?
+            .map
+            .seq
+            .iter()
+            .map(|obj| Template {
+                spell: slf.clone().unbind(),
+                id: obj.id,
+            })
+            .collect())
+    }
+
+    /// Insert the lines of a file, returning a `pyarrow.RecordBatch` with the
+    /// `line_id`, `template_id`, `template`, `parameters` and `headers` of
+    /// each line.
+    ///
+    /// Use `to_pandas()` on the batch to get a `pandas.DataFrame`. As with
+    /// `insert_many()`, other threads can't use the map meanwhile.
+    fn parse_file(&mut self, py: Python<'_>, path: PathBuf) -> PyResult<PyObject> {
+        let map = &mut self.map;
+        let batch = py.allow_threads(|| -> PyResult<RecordBatch> {
+            let content = std::fs::read_to_string(path)?;
+            table::parse_lines(map, content.lines())
+                .map_err(|e| PyValueError::new_err(e.to_string()))
+        })?;
+        batch.to_pyarrow(py)
+    }
+
+    fn __len__(&self) -> usize {
+        self.map.seq.len()
+    }
+
+    fn __str__(&self) -> String {
+        self.map.to_string()
+    }
+
+    fn __getstate__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        let mut saved = Vec::new();
+        self.map
+            .save(&mut saved, Format::Binary)
+            .map_err(to_py_err)?;
+        Ok(PyBytes::new(py, &saved))
+    }
+
+    fn __setstate__(&mut self, state: &[u8]) -> PyResult<()> {
+        self.map = LcsMap::load(state).map_err(to_py_err)?;
+        Ok(())
+    }
+}
+
+/// Template of a `Spell` map, read from the map when accessed.
+#[pyclass(module = "spell")]
+pub struct Template {
+    spell: Py<Spell>,
+    #[pyo3(get)]
+    id: TemplateId,
+}
+
+impl Template {
+    fn with_object<R>(
+        &self,
+        py: Python<'_>,
+        f: impl FnOnce(&LcsMap, &LcsObject) -> R,
+    ) -> PyResult<R> {
+        let spell = self.spell.try_borrow(py)?;
+        let obj = spell
+            .map
+            .get(self.id)
+            .ok_or_else(|| PyKeyError::new_err(self.id))?;
+        Ok(f(&spell.map, obj))
+    }
+}
+
+#[pymethods]
+impl Template {
+    /// Tokens of the template, as `(text, is_wildcard)` pairs. The text of
+    /// the wildcards is `*`.
+    fn tokens(&self, py: Python<'_>) -> PyResult<Vec<(String, bool)>> {
+        self.with_object(py, |map, obj| {
+            obj.template(&map.interner)
+                .into_iter()
+                .zip(&obj.tokens)
+                .map(|(text, token)| (text.to_string(), token.is_wildcard()))
+                .collect()
+        })
+    }
+
+    /// Identifiers of the lines matching the template.
+    fn line_ids(&self, py: Python<'_>) -> PyResult<Vec<LineId>> {
+        self.with_object(py, |_map, obj| obj.lines_ids.to_vec())
+    }
+
+    /// Number of lines matching the template.
+    fn count(&self, py: Python<'_>) -> PyResult<usize> {
+        self.with_object(py, |_map, obj| obj.count())
+    }
+
+    fn __str__(&self, py: Python<'_>) -> PyResult<String> {
+        self.with_object(py, |map, obj| obj.template(&map.interner).join(" "))
+    }
+
+    fn __repr__(&self, py: Python<'_>) -> PyResult<String> {
+        Ok(format!("Template({}, {:?})", self.id, self.__str__(py)?))
+    }
+}
+
+#[pymodule]
+fn _spell(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Spell>()?;
+    m.add_class::<Template>()?;
+    Ok(())
+}
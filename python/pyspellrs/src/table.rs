@@ -0,0 +1,221 @@
+//! Lines parsed by a map, as an Arrow record batch.
+
+use std::{collections::HashMap, sync::Arc};
+
+use arrow::{
+    array::{
+        ArrayRef, DictionaryArray, ListBuilder, MapBuilder, StringArray, StringBuilder, UInt64Array,
+    },
+    datatypes::{DataType, Field, Fields, Schema, UInt32Type},
+    error::ArrowError,
+    record_batch::RecordBatch,
+};
+
+use spell::{map::LcsMap, TemplateId};
+
+/// Columns of the table returned by `parse_lines()`.
+pub fn schema() -> Schema {
+    Schema::new(vec![
+        Field::new("line_id", DataType::UInt64, false),
+        Field::new("template_id", DataType::UInt64, false),
+        Field::new(
+            "template",
+            DataType::Dictionary(Box::new(DataType::UInt32), Box::new(DataType::Utf8)),
+            false,
+        ),
+        Field::new(
+            "parameters",
+            DataType::List(Arc::new(Field::new("item", DataType::Utf8, true))),
+            false,
+        ),
+        Field::new(
+            "headers",
+            DataType::Map(
+                Arc::new(Field::new(
+                    "entries",
+                    DataType::Struct(Fields::from(vec![
+                        Field::new("keys", DataType::Utf8, false),
+                        Field::new("values", DataType::Utf8, true),
+                    ])),
+                    false,
+                )),
+                false,
+            ),
+            false,
+        ),
+    ])
+}
+
+/// Parse the lines with `LcsMap::parse()`, describing each one by its
+/// template, the values of the template's parameters and the header fields
+/// split by the map's log format.
+///
+/// The parameters are the ones of the template when the line was parsed,
+/// unmasked, while the `template` column shows the templates once all the
+/// lines are in the map. The tokens of a parameter spanning several tokens
+/// are joined with spaces.
+pub fn parse_lines<'a>(
+    map: &mut LcsMap,
+    lines: impl IntoIterator<Item = &'a str>,
+) -> Result<RecordBatch, ArrowError> {
+    let first_line_id = map.line_id;
+    let mut template_ids: Vec<TemplateId> = Vec::new();
+    let mut parameters = ListBuilder::new(StringBuilder::new());
+    let mut headers = MapBuilder::new(None, StringBuilder::new(), StringBuilder::new());
+    for line in lines {
+        let parsed = map.parse(line);
+        template_ids.push(parsed.template_id);
+        for param in parsed.params {
+            parameters.values().append_value(param.join(" "));
+        }
+        parameters.append(true);
+        for (name, value) in parsed.headers {
+            headers.keys().append_value(name);
+            headers.values().append_value(value);
+        }
+        headers.append(true)?;
+    }
+
+    let positions: HashMap<TemplateId, u32> = map
+        .seq
+        .iter()
+        .enumerate()
+        .map(|(position, obj)| (obj.id, position as u32))
+        .collect();
+    let templates = StringArray::from_iter_values(
+        map.seq
+            .iter()
+            .map(|obj| obj.template(&map.interner).join(" ")),
+    );
+    let keys = template_ids.iter().map(|id| positions[id]).collect();
+    let template = DictionaryArray::<UInt32Type>::try_new(keys, Arc::new(templates))?;
+
+    let line_ids = (first_line_id..first_line_id + template_ids.len()).map(|id| id as u64);
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(UInt64Array::from_iter_values(line_ids)),
+        Arc::new(UInt64Array::from_iter_values(
+            template_ids.iter().map(|&id| id as u64),
+        )),
+        Arc::new(template),
+        Arc::new(parameters.finish()),
+        Arc::new(headers.finish()),
+    ];
+    RecordBatch::try_new(Arc::new(schema()), columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Array, AsArray};
+    use spell::{log_format::LogFormat, masking::MaskingRule};
+
+    fn parameters(batch: &RecordBatch) -> Vec<Vec<String>> {
+        let parameters = batch.column_by_name("parameters").unwrap().as_list::<i32>();
+        (0..parameters.len())
+            .map(|i| {
+                let values = parameters.value(i);
+                values
+                    .as_string::<i32>()
+                    .iter()
+                    .map(|value| value.unwrap().to_string())
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn headers(batch: &RecordBatch) -> Vec<Vec<(String, String)>> {
+        let headers = batch.column_by_name("headers").unwrap().as_map();
+        (0..headers.len())
+            .map(|i| {
+                let entries = headers.value(i);
+                let names = entries.column(0).as_string::<i32>();
+                let values = entries.column(1).as_string::<i32>();
+                names
+                    .iter()
+                    .zip(values.iter())
+                    .map(|(name, value)| (name.unwrap().to_string(), value.unwrap().to_string()))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parse() {
+        let mut map = LcsMap::new();
+        map.insert("Temperature (41C) exceeds warning threshold");
+        let batch = parse_lines(
+            &mut map,
+            vec![
+                "Command Failed on: node-127",
+                "Temperature (43C) exceeds warning threshold",
+                "Command Failed on: node-128 node-234",
+            ],
+        )
+        .unwrap();
+        assert_eq!(batch.num_rows(), 3);
+
+        let column = |name| batch.column_by_name(name).unwrap();
+        let line_ids = column("line_id").as_primitive::<arrow::datatypes::UInt64Type>();
+        assert_eq!(line_ids.values(), &[1, 2, 3]);
+        let template_ids = column("template_id").as_primitive::<arrow::datatypes::UInt64Type>();
+        assert_eq!(template_ids.values(), &[1, 0, 1]);
+
+        let templates = column("template").as_dictionary::<UInt32Type>();
+        let values = templates.values().as_string::<i32>();
+        let templates: Vec<&str> = templates
+            .keys()
+            .values()
+            .iter()
+            .map(|&key| values.value(key as usize))
+            .collect();
+        assert_eq!(
+            templates,
+            [
+                "Command Failed on: *",
+                "Temperature * exceeds warning threshold",
+                "Command Failed on: *",
+            ]
+        );
+
+        // The first line created its template, which had no parameter yet
+        assert_eq!(
+            parameters(&batch),
+            vec![
+                vec![],
+                vec!["(43C)".to_string()],
+                vec!["node-128 node-234".to_string()],
+            ]
+        );
+        assert_eq!(headers(&batch), vec![vec![]; 3]);
+    }
+
+    #[test]
+    fn parse_with_log_format_and_masking() {
+        let log_format = LogFormat::new("<Time> <Host>: <Content>").unwrap();
+        let mut map = LcsMap::with_log_format(log_format);
+        map.masking = vec![MaskingRule::new(r"\d+\.\d+\.\d+\.\d+", "<IP>").unwrap()];
+        let batch = parse_lines(
+            &mut map,
+            vec![
+                "10:00 node-1: Connection from 10.0.0.1 closed",
+                "10:01 node-2: Connection from 10.0.0.2 closed",
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(
+            parameters(&batch),
+            vec![vec!["10.0.0.1".to_string()], vec!["10.0.0.2".to_string()]]
+        );
+        let header = |time: &str, host: &str| {
+            vec![
+                ("Time".to_string(), time.to_string()),
+                ("Host".to_string(), host.to_string()),
+            ]
+        };
+        assert_eq!(
+            headers(&batch),
+            vec![header("10:00", "node-1"), header("10:01", "node-2")]
+        );
+    }
+}
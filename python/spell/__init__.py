@@ -0,0 +1,3 @@
+from spell._spell import Spell, Template
+
+__all__ = ["Spell", "Template"]
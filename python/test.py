@@ -1,15 +1,44 @@
-import spell
+import pickle
 
-spell.lib.init_env_logger()
+import spell
 
-spell_map = spell.Spell()
+spell_map = spell.Spell(delimiters=[" ", ","], tau=0.5)
 
-spell_map.insert("Command Failed on: node-127,node-234")
-spell_map.insert("Command Failed on: node-128,node-234")
-spell_map.insert("Command Failed on: node-129,node-235")
+assert spell_map.insert("Command Failed on: node-127,node-234") == 0
+assert spell_map.insert("Command Failed on: node-128,node-234") == 0
+assert spell_map.insert("Command Failed on: node-129,node-235") == 0
+assert len(spell_map) == 1
 
-lcs_object = spell_map.match("Command Failed on: node-130,node-235")
-for i, token in enumerate(lcs_object.tokens()):
-    print("%d - Token: %s" % (i, token))
-for i, line_id in enumerate(lcs_object.line_ids()):
+template = spell_map.match("Command Failed on: node-130,node-235")
+for i, (token, is_wildcard) in enumerate(template.tokens()):
+    print("%d - Token: %s%s" % (i, token, " (wildcard)" if is_wildcard else ""))
+for i, line_id in enumerate(template.line_ids()):
     print("%d - Line id: %s" % (i, line_id))
+assert template.tokens() == [("Command", False), ("Failed", False), ("on:", False), ("*", True)]
+assert template.line_ids() == [0, 1, 2]
+assert spell_map.match("Temperature exceeds warning threshold") is None
+
+# Pickled maps keep learning from where they stopped
+restored = pickle.loads(pickle.dumps(spell_map))
+assert str(restored) == str(spell_map)
+restored.insert("Command Failed on: node-130,node-235")
+assert [t.count() for t in restored.templates()] == [4]
+assert [t.count() for t in spell_map.templates()] == [3]
+
+# Many lines at once, as a list or as a string of lines
+ids = spell_map.insert_many(
+    ["Temperature (41C) exceeds warning threshold", "Command Failed on: node-131,node-236"]
+)
+assert ids == [1, 0]
+assert spell_map.match_many("Temperature (42C) exceeds warning threshold\nUnknown\n") == [1, None]
+
+# Parsed files as Arrow record batches, when pyarrow is installed
+try:
+    import pyarrow
+except ImportError:
+    pyarrow = None
+if pyarrow is not None:
+    batch = spell.Spell().parse_file("../fixtures/input/var_log_messages_line_1.txt")
+    assert batch.column_names == ["line_id", "template_id", "template", "parameters", "headers"]
+    assert batch.num_rows == 1
+    print(batch.to_pandas())
@@ -0,0 +1,442 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex, RwLock,
+};
+
+use crate::{
+    config::SpellConfig,
+    interner::Interner,
+    line_ids::LineIds,
+    log_format::LogFormat,
+    map::LcsMap,
+    masking::{mask, MaskingRule},
+    object::LcsObject,
+    observer::{LcsObserver, Observers},
+    tokenizer::Tokenizer,
+    LcsDelimiters, LcsSeq, LineId, TemplateId,
+};
+
+/// Number of shards the templates are spread over.
+const SHARDS: usize = 16;
+
+/// Range of template lengths stored in each shard. The last shard also holds
+/// all the longer templates.
+const BUCKET_WIDTH: usize = 4;
+
+/// Template stored in a shard. Its identifier never changes, so it can be
+/// read without locking the template.
+#[derive(Debug)]
+struct Entry {
+    id: TemplateId,
+    object: RwLock<LcsObject>,
+    /// Lines of the template, kept out of `object` so that recording a line
+    /// doesn't lock the template. They are only moved into `object` while it
+    /// is write-locked.
+    lines_ids: Mutex<LineIds>,
+}
+
+/// Templates of a range of lengths, ordered by increasing `id`.
+type Shard = RwLock<Vec<Arc<Entry>>>;
+
+/// `LcsMap` accepting lines from several threads at once.
+///
+/// The templates are sharded by length. Since templates too short or too long
+/// for a line are pruned, a line is only compared to the templates of a few
+/// shards, and only under read locks. Recording a line that leaves its
+/// template unchanged only locks the line identifiers of that template: the
+/// template is write-locked when the line generalizes it, when the values of
+/// its wildcards are tracked, or when observers are registered. Creating a
+/// template, or moving a generalized template to the shard of its new length,
+/// are the only changes made to the shards, and happen one at a time.
+///
+/// When lines are inserted in order from a single thread, the templates are
+/// the same as with an `LcsMap`.
+///
+/// # Examples
+///
+/// ```
+/// use spell::concurrent::ConcurrentLcsMap;
+/// use std::{sync::Arc, thread};
+///
+/// let map = Arc::new(ConcurrentLcsMap::new());
+/// let handles: Vec<_> = (0..4)
+///     .map(|i| {
+///         let map = map.clone();
+///         thread::spawn(move || map.insert(&format!("Command Failed on: node-{}", i)))
+///     })
+///     .collect();
+/// handles.into_iter().for_each(|handle| {
+///     handle.join().unwrap();
+/// });
+///
+/// let map = Arc::try_unwrap(map).unwrap().into_map();
+/// assert_eq!(map.seq.len(), 1);
+/// assert_eq!(map.seq[0].template(&map.interner), vec!["Command", "Failed", "on:", "*"]);
+/// ```
+#[derive(Debug)]
+pub struct ConcurrentLcsMap<T = LcsDelimiters> {
+    interner: RwLock<Interner>,
+    shards: Vec<Shard>,
+    /// Serializes the changes to the shards.
+    structure: Mutex<()>,
+    next_template_id: AtomicUsize,
+    line_id: AtomicUsize,
+    pub tokenizer: T,
+    pub config: SpellConfig,
+    /// When set, only the content of the lines is fed to Spell.
+    pub log_format: Option<LogFormat>,
+    /// Rules applied, in order, to the content of the lines before tokenization.
+    pub masking: Vec<MaskingRule>,
+    /// Notified one at a time, `None` until an observer is registered.
+    observers: Option<Mutex<Observers>>,
+}
+
+impl ConcurrentLcsMap {
+    pub fn new() -> ConcurrentLcsMap {
+        ConcurrentLcsMap::with_tokenizer(vec![' '])
+    }
+
+    /// Constructor to create a ConcurrentLcsMap with custom matching parameters.
+    pub fn with_config(config: SpellConfig) -> ConcurrentLcsMap {
+        ConcurrentLcsMap {
+            config,
+            ..ConcurrentLcsMap::new()
+        }
+    }
+}
+
+impl Default for ConcurrentLcsMap {
+    fn default() -> ConcurrentLcsMap {
+        ConcurrentLcsMap::new()
+    }
+}
+
+impl<T: Tokenizer> ConcurrentLcsMap<T> {
+    /// Constructor to create a ConcurrentLcsMap splitting the lines with a
+    /// custom tokenizer.
+    pub fn with_tokenizer(tokenizer: T) -> ConcurrentLcsMap<T> {
+        ConcurrentLcsMap {
+            interner: RwLock::new(Interner::new()),
+            shards: (0..SHARDS).map(|_| RwLock::new(Vec::new())).collect(),
+            structure: Mutex::new(()),
+            next_template_id: AtomicUsize::new(0),
+            line_id: AtomicUsize::new(0),
+            tokenizer,
+            config: SpellConfig::default(),
+            log_format: None,
+            masking: Vec::new(),
+            observers: None,
+        }
+    }
+
+    /// Register an observer notified of the changes made to the templates.
+    /// Observers are handed over to the map returned by `into_map()`.
+    pub fn add_observer<O: LcsObserver + 'static>(&mut self, observer: O) {
+        let observers = self.observers.get_or_insert_with(Default::default);
+        observers.get_mut().unwrap().0.push(Box::new(observer));
+    }
+
+    /// Insert a line, returning the identifier of its template.
+    pub fn insert(&self, entry: &str) -> TemplateId {
+        let entry = self
+            .log_format
+            .as_ref()
+            .and_then(|log_format| log_format.parse(entry))
+            .map_or(entry, |(_headers, content)| content);
+        let masked = mask(&self.masking, entry);
+        let tokens = self.tokenizer.tokenize(masked.text());
+        self.insert_tokens(&tokens)
+    }
+
+    fn insert_tokens(&self, tokens: &[&str]) -> TemplateId {
+        let line_id = self.line_id.fetch_add(1, Ordering::SeqCst);
+
+        let mut tokenized = self.interner.read().unwrap().lookup(tokens);
+        let entry = match self.get_match(&tokenized) {
+            Some(entry) => entry,
+            None => {
+                let _structure = self.structure.lock().unwrap();
+                // Another thread may have created a matching template meanwhile
+                tokenized = self.interner.read().unwrap().lookup(tokens);
+                match self.get_match(&tokenized) {
+                    Some(entry) => entry,
+                    None => return self.create(tokens, line_id),
+                }
+            }
+        };
+
+        if self.observers.is_none() {
+            let object = entry.object.read().unwrap();
+            if object.params.is_none()
+                && !object.is_changed_by(&tokenized, tokens, self.config.algorithm)
+            {
+                // Still read-locked, so the template cannot change meanwhile
+                entry.lines_ids.lock().unwrap().push(line_id);
+                return entry.id;
+            }
+        }
+
+        if self.update(&entry, &tokenized, tokens, line_id) {
+            self.move_to_bucket(&entry);
+        }
+        entry.id
+    }
+
+    /// Insert a line in its template under the template's write lock,
+    /// returning whether the template was generalized.
+    fn update(&self, entry: &Entry, tokenized: &LcsSeq, tokens: &[&str], line_id: LineId) -> bool {
+        let mut object = entry.object.write().unwrap();
+        let mut lines_ids = entry.lines_ids.lock().unwrap();
+        let interner = self.interner.read().unwrap();
+
+        std::mem::swap(&mut object.lines_ids, &mut lines_ids);
+        let previous =
+            object.insert_symbols(tokenized, tokens, line_id, &interner, self.config.algorithm);
+        if let Some(observers) = &self.observers {
+            let mut observers = observers.lock().unwrap();
+            if let Some(previous) = &previous {
+                for observer in &mut observers.0 {
+                    observer.on_template_updated(previous, &object, &interner);
+                }
+            }
+            for observer in &mut observers.0 {
+                observer.on_line_matched(&object, line_id);
+            }
+        }
+        std::mem::swap(&mut object.lines_ids, &mut lines_ids);
+
+        previous.is_some()
+    }
+
+    /// Create a template from a line. The structure lock must be held.
+    fn create(&self, tokens: &[&str], line_id: usize) -> TemplateId {
+        let tokenized: LcsSeq = {
+            let mut interner = self.interner.write().unwrap();
+            tokens.iter().map(|s| interner.intern(s)).collect()
+        };
+        let mut object = LcsObject::with_config(tokenized, line_id, &self.config);
+        object.id = self.next_template_id.fetch_add(1, Ordering::SeqCst);
+        if let Some(observers) = &self.observers {
+            let interner = self.interner.read().unwrap();
+            for observer in &mut observers.lock().unwrap().0 {
+                observer.on_new_template(&object, &interner);
+            }
+        }
+        let bucket = bucket(object.length());
+        let entry = Arc::new(Entry {
+            id: object.id,
+            lines_ids: Mutex::new(std::mem::take(&mut object.lines_ids)),
+            object: RwLock::new(object),
+        });
+        // Identifiers are handed out in order, so the shard stays sorted
+        self.shards[bucket].write().unwrap().push(entry.clone());
+        entry.id
+    }
+
+    /// Move a template to the shard of its current length, if it changed.
+    fn move_to_bucket(&self, entry: &Arc<Entry>) {
+        let _structure = self.structure.lock().unwrap();
+        let target = bucket(entry.object.read().unwrap().length());
+        let current = self
+            .shards
+            .iter()
+            .position(|shard| {
+                let shard = shard.read().unwrap();
+                shard.binary_search_by_key(&entry.id, |e| e.id).is_ok()
+            })
+            .expect("template is in a shard");
+        if current == target {
+            return;
+        }
+
+        let mut shard = self.shards[current].write().unwrap();
+        if let Ok(position) = shard.binary_search_by_key(&entry.id, |e| e.id) {
+            shard.remove(position);
+        }
+        drop(shard);
+        let mut shard = self.shards[target].write().unwrap();
+        let position = shard.binary_search_by_key(&entry.id, |e| e.id).unwrap_err();
+        shard.insert(position, entry.clone());
+    }
+
+    /// Template with the longest LCS, the first one winning ties, searching
+    /// only the shards of the lengths that are not pruned.
+    fn get_match(&self, tokenized: &LcsSeq) -> Option<Arc<Entry>> {
+        let lengths = self.config.unpruned_lengths(tokenized.len());
+        let buckets = bucket(*lengths.start())..=bucket(*lengths.end());
+
+        let mut best: Option<(usize, Arc<Entry>)> = None;
+        for shard in &self.shards[buckets] {
+            let shard = shard.read().unwrap();
+            for entry in shard.iter() {
+                let object = entry.object.read().unwrap();
+                // Same LCS as found by the lookup stages of `LcsMap`
+                let length = if object.is_subsequence_of(tokenized) {
+                    object.constants().count()
+                } else {
+                    object.get_lcs_with(tokenized, self.config.algorithm)
+                };
+                let length =
+                    match self
+                        .config
+                        .accepted_lcs(object.length(), tokenized.len(), length)
+                    {
+                        Some(length) => length,
+                        None => continue,
+                    };
+                let is_better = match &best {
+                    None => true,
+                    Some((best_length, best)) => {
+                        length > *best_length || (length == *best_length && entry.id < best.id)
+                    }
+                };
+                if is_better {
+                    best = Some((length, entry.clone()));
+                }
+            }
+        }
+        best.map(|(_, entry)| entry)
+    }
+
+    /// Number of templates.
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.read().unwrap().len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Sequential map holding the templates learned so far.
+    pub fn into_map(self) -> LcsMap<T> {
+        let mut seq: Vec<LcsObject> = self
+            .shards
+            .into_iter()
+            .flat_map(|shard| shard.into_inner().unwrap())
+            .map(|entry| {
+                let entry =
+                    Arc::try_unwrap(entry).expect("templates are only shared during insertions");
+                let mut object = entry.object.into_inner().unwrap();
+                object.lines_ids = entry.lines_ids.into_inner().unwrap();
+                object
+            })
+            .collect();
+        seq.sort_by_key(|object| object.id);
+
+        let mut map = LcsMap::with_tokenizer(self.tokenizer);
+        map.seq = seq;
+        map.interner = self.interner.into_inner().unwrap();
+        map.next_template_id = self.next_template_id.into_inner();
+        map.line_id = self.line_id.into_inner();
+        map.config = self.config;
+        map.log_format = self.log_format;
+        map.masking = self.masking;
+        if let Some(observers) = self.observers {
+            map.observers = observers.into_inner().unwrap();
+        }
+        map
+    }
+}
+
+fn bucket(length: usize) -> usize {
+    (length / BUCKET_WIDTH).min(SHARDS - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        fixtures::{is_send_sync, var_log_messages_lines, Recorder},
+        line_ids::LineIdStorage,
+    };
+    use std::thread;
+
+    #[test]
+    fn compare_sequential() {
+        is_send_sync::<ConcurrentLcsMap>();
+
+        let inputs = var_log_messages_lines();
+        for config in [
+            SpellConfig::default(),
+            SpellConfig::default().without_pruning(),
+            SpellConfig::default().with_tau(0.3),
+            SpellConfig::default()
+                .with_line_ids(LineIdStorage::Bitmap)
+                .with_tracked_values(2),
+        ] {
+            let mut sequential = LcsMap::with_config(config.clone());
+            let concurrent = ConcurrentLcsMap::with_config(config);
+            for input in inputs.iter() {
+                sequential.insert(input);
+                concurrent.insert(input);
+            }
+
+            let concurrent = concurrent.into_map();
+            assert_eq!(concurrent, sequential);
+            assert_eq!(concurrent.to_string(), sequential.to_string());
+        }
+    }
+
+    #[test]
+    fn observers_are_handed_over() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut sequential = LcsMap::new();
+        sequential.add_observer(Recorder(events.clone()));
+        var_log_messages_lines()
+            .iter()
+            .for_each(|input| sequential.insert(input));
+        let expected = std::mem::take(&mut *events.lock().unwrap());
+
+        let mut concurrent = ConcurrentLcsMap::new();
+        concurrent.add_observer(Recorder(events.clone()));
+        let inputs = var_log_messages_lines();
+        inputs[..5].iter().for_each(|input| {
+            concurrent.insert(input);
+        });
+        let mut map = concurrent.into_map();
+        map.insert(inputs[5]);
+        assert_eq!(*events.lock().unwrap(), expected);
+    }
+
+    #[test]
+    fn insert_from_threads() {
+        let map = Arc::new(ConcurrentLcsMap::new());
+        let handles: Vec<_> = (0..4)
+            .map(|thread| {
+                let map = map.clone();
+                thread::spawn(move || {
+                    for i in 0..50 {
+                        map.insert(&format!("Command Failed on: node-{}-{}", thread, i));
+                        map.insert(&format!(
+                            "Temperature ({}C) exceeds warning threshold",
+                            thread * i
+                        ));
+                    }
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .for_each(|handle| handle.join().unwrap());
+
+        assert_eq!(map.len(), 2);
+        let map = Arc::try_unwrap(map).unwrap().into_map();
+        let mut templates: Vec<_> = map
+            .seq
+            .iter()
+            .map(|obj| (obj.template(&map.interner).join(" "), obj.count()))
+            .collect();
+        templates.sort();
+        assert_eq!(
+            templates,
+            vec![
+                ("Command Failed on: *".to_string(), 200),
+                ("Temperature * exceeds warning threshold".to_string(), 200),
+            ]
+        );
+        assert_eq!(map.line_id, 400);
+    }
+}
@@ -0,0 +1,274 @@
+use std::ops::RangeInclusive;
+
+use serde_derive::*;
+
+use crate::{lcs::LcsAlgorithm, line_ids::LineIdStorage};
+
+/// Parameters controlling how a line is matched against the existing templates.
+///
+/// The defaults reproduce the values used in the Spell paper.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpellConfig {
+    /// Minimum ratio of the line's tokens that must be part of the LCS for a
+    /// template to be accepted as a match (the paper's _tau_). It is clamped
+    /// to `0.0..=1.0` when used, NaN giving the default.
+    pub tau: f64,
+    /// Templates whose length falls outside these bounds are not considered
+    /// for a match. `None` disables pruning.
+    pub pruning: Option<PruningBounds>,
+    /// How the templates compared against a line are selected.
+    pub candidates: CandidateSelection,
+    /// Algorithm used both to compare a line to the templates and to merge a
+    /// line into its template.
+    pub algorithm: LcsAlgorithm,
+    /// How the identifiers of the lines are kept by new templates.
+    pub line_ids: LineIdStorage,
+    /// Most distinct values tracked by new templates for `LcsMap::refine()`.
+    /// The limit applies to each wildcard on its own: once a wildcard takes
+    /// more distinct values, its values are dropped and it stops being
+    /// tracked, so it is never split. `None` disables tracking, which also
+    /// requires `line_ids` to keep all the identifiers.
+    pub tracked_values: Option<usize>,
+}
+
+/// Length window, relative to the length of the line, outside of which
+/// templates are skipped.
+///
+/// `min_ratio` is clamped to `0.0..=1.0` and `max_ratio` to at least `1.0`
+/// when used, NaN giving the defaults.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PruningBounds {
+    pub min_ratio: f64,
+    pub max_ratio: f64,
+}
+
+/// Selection of the templates whose LCS with a line is computed.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum CandidateSelection {
+    /// Every template is a candidate.
+    All,
+    /// Only templates sharing at least one constant token with the line are
+    /// candidates, ranked by the number of shared tokens. When `top_k` is set,
+    /// only the best ranked ones are compared, which can miss the best match.
+    SharedTokens { top_k: Option<usize> },
+}
+
+const DEFAULT_TAU: f64 = 0.5;
+const DEFAULT_MIN_RATIO: f64 = 0.5;
+const DEFAULT_MAX_RATIO: f64 = 2.0;
+
+impl Default for SpellConfig {
+    fn default() -> SpellConfig {
+        SpellConfig {
+            tau: DEFAULT_TAU,
+            pruning: Some(PruningBounds::default()),
+            candidates: CandidateSelection::All,
+            algorithm: LcsAlgorithm::Greedy,
+            line_ids: LineIdStorage::All,
+            tracked_values: None,
+        }
+    }
+}
+
+impl Default for PruningBounds {
+    fn default() -> PruningBounds {
+        PruningBounds {
+            min_ratio: DEFAULT_MIN_RATIO,
+            max_ratio: DEFAULT_MAX_RATIO,
+        }
+    }
+}
+
+impl SpellConfig {
+    /// `tau` is clamped to `0.0..=1.0`, NaN giving the default.
+    pub fn with_tau(self, tau: f64) -> SpellConfig {
+        SpellConfig {
+            tau: clamp_tau(tau),
+            ..self
+        }
+    }
+
+    /// `min_ratio` is clamped to `0.0..=1.0` and `max_ratio` to at least
+    /// `1.0`, NaN giving the defaults.
+    pub fn with_pruning(self, min_ratio: f64, max_ratio: f64) -> SpellConfig {
+        let bounds = PruningBounds {
+            min_ratio,
+            max_ratio,
+        };
+        SpellConfig {
+            pruning: Some(bounds.clamped()),
+            ..self
+        }
+    }
+
+    pub fn without_pruning(self) -> SpellConfig {
+        SpellConfig {
+            pruning: None,
+            ..self
+        }
+    }
+
+    pub fn with_candidates(self, candidates: CandidateSelection) -> SpellConfig {
+        SpellConfig { candidates, ..self }
+    }
+
+    pub fn with_algorithm(self, algorithm: LcsAlgorithm) -> SpellConfig {
+        SpellConfig { algorithm, ..self }
+    }
+
+    pub fn with_line_ids(self, line_ids: LineIdStorage) -> SpellConfig {
+        SpellConfig { line_ids, ..self }
+    }
+
+    pub fn with_tracked_values(self, limit: usize) -> SpellConfig {
+        SpellConfig {
+            tracked_values: Some(limit),
+            ..self
+        }
+    }
+
+    /// Minimum LCS length for a line of `line_length` tokens to match a template.
+    pub fn threshold(&self, line_length: usize) -> usize {
+        scale(line_length, clamp_tau(self.tau))
+    }
+
+    /// Whether a template of `object_length` tokens is skipped for a line of
+    /// `line_length` tokens.
+    pub fn is_pruned(&self, object_length: usize, line_length: usize) -> bool {
+        !self.unpruned_lengths(line_length).contains(&object_length)
+    }
+
+    /// Lengths of the templates not skipped for a line of `line_length` tokens.
+    pub fn unpruned_lengths(&self, line_length: usize) -> RangeInclusive<usize> {
+        match self.pruning {
+            None => 0..=usize::MAX,
+            Some(bounds) => {
+                let bounds = bounds.clamped();
+                scale(line_length, bounds.min_ratio)..=scale(line_length, bounds.max_ratio)
+            }
+        }
+    }
+
+    /// Applies the pruning and threshold criteria to an LCS `length` between a
+    /// template of `object_length` tokens and a line of `line_length` tokens.
+    pub(crate) fn accepted_lcs(
+        &self,
+        object_length: usize,
+        line_length: usize,
+        length: usize,
+    ) -> Option<usize> {
+        // Pruning as described in paper
+        if self.is_pruned(object_length, line_length) {
+            None
+        } else if length > 0 && length >= self.threshold(line_length) {
+            Some(length)
+        } else {
+            None
+        }
+    }
+}
+
+impl PruningBounds {
+    fn clamped(self) -> PruningBounds {
+        PruningBounds {
+            min_ratio: clamp(self.min_ratio, 0.0, 1.0, DEFAULT_MIN_RATIO),
+            max_ratio: clamp(self.max_ratio, 1.0, f64::INFINITY, DEFAULT_MAX_RATIO),
+        }
+    }
+}
+
+fn clamp_tau(tau: f64) -> f64 {
+    clamp(tau, 0.0, 1.0, DEFAULT_TAU)
+}
+
+fn clamp(value: f64, min: f64, max: f64, default: f64) -> f64 {
+    if value.is_nan() {
+        default
+    } else {
+        value.clamp(min, max)
+    }
+}
+
+fn scale(length: usize, ratio: f64) -> usize {
+    (length as f64 * ratio).floor() as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_match_paper() {
+        let config = SpellConfig::default();
+        for length in 0..100 {
+            assert_eq!(config.threshold(length), length / 2);
+            for object_length in 0..250 {
+                assert_eq!(
+                    config.is_pruned(object_length, length),
+                    object_length < length / 2 || object_length > length * 2
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn pruning_can_be_disabled() {
+        let config = SpellConfig::default().without_pruning();
+        assert!(!config.is_pruned(1, 100));
+        assert!(!config.is_pruned(1000, 1));
+    }
+
+    #[test]
+    fn custom_bounds() {
+        let config = SpellConfig::default().with_tau(0.8).with_pruning(0.9, 1.1);
+        assert_eq!(config.threshold(10), 8);
+        assert!(config.is_pruned(8, 10));
+        assert!(!config.is_pruned(9, 10));
+        assert!(!config.is_pruned(11, 10));
+        assert!(config.is_pruned(12, 10));
+        assert_eq!(config.unpruned_lengths(10), 9..=11);
+    }
+
+    #[test]
+    fn invalid_values_are_clamped() {
+        assert_eq!(SpellConfig::default().with_tau(f64::NAN).tau, 0.5);
+        assert_eq!(SpellConfig::default().with_tau(-1.0).tau, 0.0);
+        assert_eq!(SpellConfig::default().with_tau(1.5).tau, 1.0);
+
+        let config = SpellConfig::default().with_pruning(-0.5, 0.5);
+        assert_eq!(
+            config.pruning,
+            Some(PruningBounds {
+                min_ratio: 0.0,
+                max_ratio: 1.0
+            })
+        );
+        assert_eq!(config.unpruned_lengths(10), 0..=10);
+        let config = SpellConfig::default().with_pruning(f64::NAN, f64::NAN);
+        assert_eq!(config.pruning, Some(PruningBounds::default()));
+    }
+
+    #[test]
+    fn invalid_fields_are_clamped_when_used() {
+        let config = SpellConfig {
+            tau: f64::NAN,
+            pruning: Some(PruningBounds {
+                min_ratio: f64::NAN,
+                max_ratio: 0.5,
+            }),
+            ..SpellConfig::default()
+        };
+        assert_eq!(config.threshold(10), 5);
+        assert_eq!(config.unpruned_lengths(10), 5..=10);
+        assert_eq!(config.accepted_lcs(10, 10, 4), None);
+        assert_eq!(config.accepted_lcs(10, 10, 5), Some(5));
+
+        let config: SpellConfig = serde_json::from_str(
+            r#"{"tau": 7.0, "pruning": {"min_ratio": -1.0, "max_ratio": 1e9}}"#,
+        )
+        .unwrap();
+        assert_eq!(config.threshold(10), 10);
+        assert_eq!(config.unpruned_lengths(10), 0..=10_000_000_000);
+    }
+}
@@ -0,0 +1,89 @@
+use crate::LineId;
+
+/// Errors returned by this crate.
+#[derive(Debug)]
+pub enum Error {
+    /// A log format specification is invalid.
+    LogFormat(String),
+    /// A regular expression failed to compile.
+    Regex(regex::Error),
+    /// Reading or writing a saved map failed.
+    Io(std::io::Error),
+    /// A saved map doesn't start with a valid header.
+    InvalidHeader(String),
+    /// A saved map was written with a newer, unknown, schema version.
+    UnsupportedVersion(u32),
+    /// Encoding or decoding a map in JSON failed.
+    Json(serde_json::Error),
+    /// Encoding or decoding a map in the binary format failed.
+    Binary(bincode::Error),
+    /// A saved map refers to tokens or wildcards that it doesn't have.
+    InvalidMap(String),
+    /// Lines merged from another map, numbered from the first identifier,
+    /// would reuse identifiers below the second one, already given to lines.
+    OverlappingLines(LineId, LineId),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::LogFormat(message) => write!(f, "Invalid log format: {}", message),
+            Error::Regex(e) => write!(f, "Invalid regular expression: {}", e),
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::InvalidHeader(header) => write!(f, "Invalid saved map header: {:?}", header),
+            Error::UnsupportedVersion(version) => {
+                write!(f, "Unsupported saved map version: {}", version)
+            }
+            Error::Json(e) => write!(f, "JSON error: {}", e),
+            Error::Binary(e) => write!(f, "Binary format error: {}", e),
+            Error::InvalidMap(message) => write!(f, "Invalid saved map: {}", message),
+            Error::OverlappingLines(start, next) => write!(
+                f,
+                "Merged lines numbered from {} would reuse identifiers below {}",
+                start, next
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::LogFormat(_)
+            | Error::InvalidHeader(_)
+            | Error::UnsupportedVersion(_)
+            | Error::InvalidMap(_)
+            | Error::OverlappingLines(..) => None,
+            Error::Regex(e) => Some(e),
+            Error::Io(e) => Some(e),
+            Error::Json(e) => Some(e),
+            Error::Binary(e) => Some(e),
+        }
+    }
+}
+
+impl From<regex::Error> for Error {
+    fn from(e: regex::Error) -> Error {
+        Error::Regex(e)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Error {
+        Error::Json(e)
+    }
+}
+
+impl From<bincode::Error> for Error {
+    fn from(e: bincode::Error) -> Error {
+        Error::Binary(e)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,47 @@
+//! Inputs and helpers shared by the tests of the modules.
+
+use std::sync::{Arc, Mutex};
+
+use crate::{interner::Interner, object::LcsObject, observer::LcsObserver, LcsTemplate, LineId};
+
+pub fn var_log_messages_lines() -> [&'static str; 6] {
+    [
+        include_str!("../fixtures/input/var_log_messages_line_1.txt"),
+        include_str!("../fixtures/input/var_log_messages_line_2.txt"),
+        include_str!("../fixtures/input/var_log_messages_line_3.txt"),
+        include_str!("../fixtures/input/var_log_messages_line_4.txt"),
+        include_str!("../fixtures/input/var_log_messages_line_5.txt"),
+        include_str!("../fixtures/input/var_log_messages_line_6.txt"),
+    ]
+}
+
+/// Compiles only if `S` can be shared between threads.
+pub fn is_send_sync<S: Send + Sync>() {}
+
+/// Observer recording the events it is notified of, as strings.
+pub struct Recorder(pub Arc<Mutex<Vec<String>>>);
+
+impl LcsObserver for Recorder {
+    fn on_new_template(&mut self, template: &LcsObject, _interner: &Interner) {
+        self.0.lock().unwrap().push(format!("new {}", template.id));
+    }
+
+    fn on_template_updated(&mut self, old: &LcsTemplate, new: &LcsObject, interner: &Interner) {
+        self.0.lock().unwrap().push(format!(
+            "updated {}: {} -> {}",
+            new.id,
+            old.iter()
+                .map(|s| s.resolve(interner))
+                .collect::<Vec<_>>()
+                .join(" "),
+            new.template(interner).join(" ")
+        ));
+    }
+
+    fn on_line_matched(&mut self, template: &LcsObject, line_id: LineId) {
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("matched {} {}", template.id, line_id));
+    }
+}
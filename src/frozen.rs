@@ -0,0 +1,103 @@
+use crate::{
+    map::LcsMap, object::LcsObject, observer::Observers, tokenizer::Tokenizer, TemplateId,
+};
+
+/// Read-only `LcsMap`, used to classify lines against templates learned
+/// beforehand.
+///
+/// Classifying a line never modifies the templates nor their line
+/// identifiers, so a frozen map can be shared between threads behind an
+/// `Arc`.
+///
+/// # Examples
+///
+/// ```
+/// use spell::map::LcsMap;
+///
+/// let mut map = LcsMap::new();
+/// map.insert("Command Failed on: node-127");
+/// map.insert("Command Failed on: node-234");
+///
+/// let frozen = map.freeze();
+/// assert_eq!(frozen.classify("Command Failed on: node-1"), Some(0));
+/// assert_eq!(frozen.classify("Server started"), None);
+/// ```
+#[derive(Debug)]
+pub struct FrozenLcsMap<T = crate::LcsDelimiters> {
+    map: LcsMap<T>,
+}
+
+impl<T: Tokenizer> LcsMap<T> {
+    /// Stop learning and only classify lines from now on.
+    ///
+    /// The observers are dropped as they will not be notified anymore.
+    pub fn freeze(mut self) -> FrozenLcsMap<T> {
+        self.observers = Observers::default();
+        self.rebuild_index();
+        FrozenLcsMap { map: self }
+    }
+}
+
+impl<T: Tokenizer> FrozenLcsMap<T> {
+    /// Identifier of the template matching the line, or `None` if the line
+    /// is unknown. See `LcsMap::classify()`.
+    pub fn classify(&self, entry: &str) -> Option<TemplateId> {
+        self.map.classify(entry)
+    }
+
+    /// Template with the given identifier.
+    pub fn get(&self, id: TemplateId) -> Option<&LcsObject> {
+        self.map.get(id)
+    }
+
+    /// Map the templates were learned in.
+    pub fn map(&self) -> &LcsMap<T> {
+        &self.map
+    }
+
+    /// Resume learning.
+    pub fn into_map(self) -> LcsMap<T> {
+        self.map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::is_send_sync;
+    use std::{sync::Arc, thread};
+
+    #[test]
+    fn classify() {
+        is_send_sync::<FrozenLcsMap>();
+
+        let mut map = LcsMap::new();
+        map.insert("Temperature (41C) exceeds warning threshold");
+        map.insert("Temperature (42C, 43C) exceeds warning threshold");
+        map.insert("Command Failed on: node-127");
+        map.insert("Command Failed on: node-234");
+        let expected = map.to_string();
+
+        let frozen = Arc::new(map.freeze());
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let frozen = frozen.clone();
+                thread::spawn(move || {
+                    vec![
+                        frozen.classify(&format!("Temperature ({}C) exceeds warning threshold", i)),
+                        frozen.classify(&format!("Command Failed on: node-{}", i)),
+                        frozen.classify("Server started"),
+                        frozen.classify(""),
+                    ]
+                })
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), vec![Some(0), Some(1), None, None]);
+        }
+
+        // Classifying doesn't modify the templates nor their lines
+        assert_eq!(frozen.map().to_string(), expected);
+        assert_eq!(frozen.get(0).unwrap().lines_ids.to_vec(), vec![0, 1]);
+    }
+}
@@ -0,0 +1,145 @@
+use std::{collections::HashMap, sync::Arc};
+
+use serde::{Deserializer, Serializer};
+use serde_derive::*;
+
+use crate::LcsSeq;
+
+/// Identifier of a token interned by an `Interner`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    /// Stands for the tokens of a line unknown to the interner. As these are
+    /// not part of any template, they can't match a template token.
+    pub const UNKNOWN: Symbol = Symbol(u32::MAX);
+}
+
+/// Strings of the tokens, shared by all the templates of an `LcsMap`.
+///
+/// Only the tokens of the templates are interned, so that the interner
+/// doesn't grow with the variable parts of the lines.
+#[derive(Clone, PartialEq)]
+pub struct Interner {
+    strings: Vec<Arc<str>>,
+    symbols: HashMap<Arc<str>, Symbol>,
+}
+
+impl Interner {
+    pub fn new() -> Interner {
+        Interner {
+            strings: Vec::new(),
+            symbols: HashMap::new(),
+        }
+    }
+
+    /// Symbol of `token`, interning it if needed.
+    pub fn intern(&mut self, token: &str) -> Symbol {
+        if let Some(&symbol) = self.symbols.get(token) {
+            return symbol;
+        }
+        let symbol = Symbol(self.strings.len() as u32);
+        let token: Arc<str> = Arc::from(token);
+        self.strings.push(token.clone());
+        self.symbols.insert(token, symbol);
+        symbol
+    }
+
+    /// Symbol of `token`, if it was interned.
+    pub fn get(&self, token: &str) -> Option<Symbol> {
+        self.symbols.get(token).cloned()
+    }
+
+    /// Symbols of the tokens of a line, without interning them. Tokens never
+    /// interned are `Symbol::UNKNOWN`.
+    pub fn lookup(&self, tokens: &[&str]) -> LcsSeq {
+        tokens
+            .iter()
+            .map(|token| self.get(token).unwrap_or(Symbol::UNKNOWN))
+            .collect()
+    }
+
+    /// String of a symbol.
+    ///
+    /// # Panics
+    ///
+    /// If the symbol was not returned by this interner.
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        &self.strings[symbol.0 as usize]
+    }
+
+    pub fn resolve_all(&self, symbols: &[Symbol]) -> Vec<&str> {
+        symbols.iter().map(|&symbol| self.resolve(symbol)).collect()
+    }
+
+    /// Whether the symbol was returned by this interner.
+    pub fn contains(&self, symbol: Symbol) -> bool {
+        (symbol.0 as usize) < self.strings.len()
+    }
+
+    /// Number of interned tokens.
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
+
+impl Default for Interner {
+    fn default() -> Interner {
+        Interner::new()
+    }
+}
+
+impl std::fmt::Debug for Interner {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_list().entries(&self.strings).finish()
+    }
+}
+
+// Serialized as the list of strings, symbols being their positions.
+impl serde::Serialize for Interner {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.strings.iter().map(|s| &**s))
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Interner {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Interner, D::Error> {
+        let strings: Vec<String> = serde::Deserialize::deserialize(deserializer)?;
+        let mut interner = Interner {
+            strings: Vec::with_capacity(strings.len()),
+            symbols: HashMap::with_capacity(strings.len()),
+        };
+        for s in &strings {
+            interner.intern(s);
+        }
+        if interner.len() != strings.len() {
+            return Err(serde::de::Error::custom("duplicated interned token"));
+        }
+        Ok(interner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intern_and_resolve() {
+        let mut interner = Interner::new();
+        assert!(interner.is_empty());
+
+        let a = interner.intern("a");
+        let b = interner.intern("b");
+        assert_ne!(a, b);
+        assert_eq!(interner.intern("a"), a);
+        assert_eq!(interner.len(), 2);
+        assert_eq!(interner.resolve_all(&[b, a]), ["b", "a"]);
+        assert_eq!(interner.lookup(&["a", "c"]), vec![a, Symbol::UNKNOWN]);
+        assert_eq!(interner.len(), 2);
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::LcsToken;
+
+/// Inverted index from each constant token to the templates containing it.
+///
+/// Templates are referenced by their index in `LcsMap::seq`. Like the prefix
+/// tree, the index is a cache derived from the templates and is never
+/// serialized.
+#[derive(Default, Clone)]
+pub(crate) struct InvertedIndex {
+    /// For each token, the templates containing it and how many times.
+    postings: HashMap<LcsToken, Vec<(usize, usize)>>,
+    len: usize,
+}
+
+impl InvertedIndex {
+    pub fn new() -> InvertedIndex {
+        Default::default()
+    }
+
+    /// Number of templates stored in the index.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn insert<'a, I>(&mut self, index: usize, constants: I)
+    where
+        I: IntoIterator<Item = &'a LcsToken>,
+    {
+        for (token, count) in occurrences(constants) {
+            self.postings
+                .entry(*token)
+                .or_default()
+                .push((index, count));
+        }
+        self.len += 1;
+    }
+
+    pub fn remove<'a, I>(&mut self, index: usize, constants: I)
+    where
+        I: IntoIterator<Item = &'a LcsToken>,
+    {
+        for (token, _count) in occurrences(constants) {
+            if let Some(postings) = self.postings.get_mut(token) {
+                postings.retain(|&(i, _)| i != index);
+                if postings.is_empty() {
+                    self.postings.remove(token);
+                }
+            }
+        }
+        self.len = self.len.saturating_sub(1);
+    }
+
+    /// Templates sharing at least one token with `tokenized`, along with the
+    /// number of their constant tokens found in `tokenized`.
+    ///
+    /// That number is an upper bound of the LCS. The candidates are sorted by
+    /// decreasing number of shared tokens, then by index.
+    pub fn candidates(&self, tokenized: &[LcsToken]) -> Vec<(usize, usize)> {
+        let distinct: HashSet<&LcsToken> = tokenized.iter().collect();
+
+        let mut scores: HashMap<usize, usize> = HashMap::new();
+        for token in distinct {
+            for &(index, count) in self.postings.get(token).into_iter().flatten() {
+                *scores.entry(index).or_insert(0) += count;
+            }
+        }
+
+        let mut candidates: Vec<_> = scores.into_iter().collect();
+        candidates.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        candidates
+    }
+}
+
+fn occurrences<'a, I>(constants: I) -> HashMap<&'a LcsToken, usize>
+where
+    I: IntoIterator<Item = &'a LcsToken>,
+{
+    let mut counts = HashMap::new();
+    for token in constants {
+        *counts.entry(token).or_insert(0) += 1;
+    }
+    counts
+}
+
+impl std::fmt::Debug for InvertedIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("InvertedIndex")
+            .field("tokens", &self.postings.len())
+            .field("templates", &self.len)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interner::Interner;
+
+    #[test]
+    fn candidates_are_ranked() {
+        let mut interner = Interner::new();
+        let mut seq =
+            |s: &str| -> Vec<LcsToken> { s.split(' ').map(|s| interner.intern(s)).collect() };
+        let mut index = InvertedIndex::new();
+        index.insert(0, &seq("Command Failed on"));
+        index.insert(1, &seq("Command Succeeded"));
+        index.insert(2, &seq("user user logged in"));
+        assert_eq!(index.len(), 3);
+
+        let candidates = index.candidates(&seq("on node-1 Command Failed"));
+        assert_eq!(candidates, vec![(0, 3), (1, 1)]);
+
+        // Repeated tokens are counted as many times as they appear in the template
+        let candidates = index.candidates(&seq("user in"));
+        assert_eq!(candidates, vec![(2, 3)]);
+    }
+
+    #[test]
+    fn remove() {
+        let mut interner = Interner::new();
+        let mut seq =
+            |s: &str| -> Vec<LcsToken> { s.split(' ').map(|s| interner.intern(s)).collect() };
+        let mut index = InvertedIndex::new();
+        index.insert(0, &seq("a b c"));
+        index.insert(1, &seq("a b"));
+        index.remove(0, &seq("a b c"));
+        index.insert(0, &seq("a c"));
+        assert_eq!(index.len(), 2);
+
+        assert_eq!(index.candidates(&seq("a b c")), vec![(0, 2), (1, 2)]);
+        assert_eq!(index.candidates(&seq("b")), vec![(1, 1)]);
+    }
+}
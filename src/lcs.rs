@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+
+use serde_derive::*;
+
+use crate::{template::TemplateToken, LcsToken};
+
+/// Algorithm used to compute the longest common subsequence between a
+/// template and a line.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LcsAlgorithm {
+    /// Single pass loop from the paper, matching each token of the template to
+    /// the next occurrence in the line. Fast, but can undercount the LCS when
+    /// an early match consumes a token a later one needed.
+    #[default]
+    Greedy,
+    /// Classic dynamic programming, in `O(n * m)` time and memory.
+    DynamicProgramming,
+    /// Hunt–Szymanski, in `O((r + n) log n)` time where `r` is the number of
+    /// matching token pairs. Best suited for lines with few repeated tokens.
+    HuntSzymanski,
+}
+
+/// Whether a token of the template matches a token of the line. Wildcards
+/// never match.
+fn matches(left: &TemplateToken, right: &LcsToken) -> bool {
+    *left == TemplateToken::Constant(*right)
+}
+
+/// Length of the LCS between the constant tokens of `template` and `other`.
+pub fn length(template: &[TemplateToken], other: &[LcsToken], algorithm: LcsAlgorithm) -> usize {
+    match algorithm {
+        LcsAlgorithm::Greedy => {
+            let mut count = 0;
+            let mut last_match = 0;
+            for s_left in template.iter().filter(|s| !s.is_wildcard()) {
+                if let Some(j) = other[last_match.min(other.len())..]
+                    .iter()
+                    .position(|s_right| matches(s_left, s_right))
+                {
+                    last_match += j + 1;
+                    count += 1;
+                }
+            }
+            count
+        }
+        LcsAlgorithm::DynamicProgramming => {
+            // Only two rows of the table are needed for the length
+            let mut previous = vec![0; other.len() + 1];
+            let mut current = vec![0; other.len() + 1];
+            for s_left in template {
+                for (j, s_right) in other.iter().enumerate() {
+                    current[j + 1] = if matches(s_left, s_right) {
+                        previous[j] + 1
+                    } else {
+                        previous[j + 1].max(current[j])
+                    };
+                }
+                std::mem::swap(&mut previous, &mut current);
+            }
+            previous[other.len()]
+        }
+        LcsAlgorithm::HuntSzymanski => hunt_szymanski(template, other).len(),
+    }
+}
+
+/// Pairs of indices `(i, j)`, increasing in both, such that `template[i]` is
+/// the constant `other[j]`, are the tokens of the LCS. Wildcards in `template`
+/// are never matched.
+pub fn alignment(
+    template: &[TemplateToken],
+    other: &[LcsToken],
+    algorithm: LcsAlgorithm,
+) -> Vec<(usize, usize)> {
+    match algorithm {
+        LcsAlgorithm::Greedy => greedy(template, other),
+        LcsAlgorithm::DynamicProgramming => dynamic_programming(template, other),
+        LcsAlgorithm::HuntSzymanski => hunt_szymanski(template, other),
+    }
+}
+
+fn greedy(template: &[TemplateToken], other: &[LcsToken]) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+
+    // Simple loop implementation from the paper
+    let mut last_match = 0;
+    for (i, s_left) in template.iter().enumerate() {
+        if s_left.is_wildcard() {
+            continue;
+        }
+        for (j, s_right) in other.iter().enumerate().skip(last_match) {
+            if matches(s_left, s_right) {
+                last_match = j + 1;
+                pairs.push((i, j));
+                break;
+            }
+        }
+    }
+
+    pairs
+}
+
+fn dynamic_programming(template: &[TemplateToken], other: &[LcsToken]) -> Vec<(usize, usize)> {
+    let (n, m) = (template.len(), other.len());
+    let width = m + 1;
+
+    // table[i * width + j] is the LCS of template[i..] and other[j..]
+    let mut table = vec![0usize; (n + 1) * width];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            table[i * width + j] = if matches(&template[i], &other[j]) {
+                table[(i + 1) * width + j + 1] + 1
+            } else {
+                table[(i + 1) * width + j].max(table[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut pairs = Vec::with_capacity(table[0]);
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if matches(&template[i], &other[j]) {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+        } else if table[(i + 1) * width + j] >= table[i * width + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    pairs
+}
+
+fn hunt_szymanski(template: &[TemplateToken], other: &[LcsToken]) -> Vec<(usize, usize)> {
+    // Positions of each token in `other`, in decreasing order
+    let mut positions: HashMap<LcsToken, Vec<usize>> = HashMap::new();
+    for (j, token) in other.iter().enumerate().rev() {
+        positions.entry(*token).or_default().push(j);
+    }
+
+    // thresholds[k] is the smallest index in `other` ending a common
+    // subsequence of length k + 1, and links[k] the last node of that subsequence.
+    let mut thresholds: Vec<usize> = Vec::new();
+    let mut links: Vec<usize> = Vec::new();
+    // Nodes of the subsequences: (i, j, previous node)
+    let mut nodes: Vec<(usize, usize, Option<usize>)> = Vec::new();
+
+    for (i, token) in template.iter().enumerate() {
+        let token = match token.constant() {
+            Some(token) => token,
+            None => continue,
+        };
+        for &j in positions.get(&token).into_iter().flatten() {
+            let k = match thresholds.binary_search(&j) {
+                Ok(_) => continue,
+                Err(k) => k,
+            };
+            let previous = if k > 0 { Some(links[k - 1]) } else { None };
+            nodes.push((i, j, previous));
+            if k == thresholds.len() {
+                thresholds.push(j);
+                links.push(nodes.len() - 1);
+            } else {
+                thresholds[k] = j;
+                links[k] = nodes.len() - 1;
+            }
+        }
+    }
+
+    let mut pairs = Vec::with_capacity(links.len());
+    let mut node = links.last().cloned();
+    while let Some(index) = node {
+        let (i, j, previous) = nodes[index];
+        pairs.push((i, j));
+        node = previous;
+    }
+    pairs.reverse();
+
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interner::Interner;
+
+    const ALGORITHMS: [LcsAlgorithm; 3] = [
+        LcsAlgorithm::Greedy,
+        LcsAlgorithm::DynamicProgramming,
+        LcsAlgorithm::HuntSzymanski,
+    ];
+
+    fn seq(interner: &mut Interner, s: &str) -> Vec<LcsToken> {
+        s.split(' ')
+            .filter(|s| !s.is_empty())
+            .map(|s| interner.intern(s))
+            .collect()
+    }
+
+    /// Template where `*` are wildcards
+    fn template(interner: &mut Interner, s: &str) -> Vec<TemplateToken> {
+        s.split(' ')
+            .filter(|s| !s.is_empty())
+            .map(|s| match s {
+                "*" => TemplateToken::wildcard(),
+                _ => TemplateToken::Constant(interner.intern(s)),
+            })
+            .collect()
+    }
+
+    fn check_alignment(template: &[TemplateToken], other: &[LcsToken], pairs: &[(usize, usize)]) {
+        for window in pairs.windows(2) {
+            assert!(window[0].0 < window[1].0 && window[0].1 < window[1].1);
+        }
+        for &(i, j) in pairs {
+            assert_eq!(template[i], TemplateToken::Constant(other[j]));
+        }
+    }
+
+    #[test]
+    fn greedy_can_undercount() {
+        let mut interner = Interner::new();
+        let template = template(&mut interner, "a b c");
+        let other = seq(&mut interner, "b c a");
+        assert_eq!(length(&template, &other, LcsAlgorithm::Greedy), 1);
+        assert_eq!(
+            length(&template, &other, LcsAlgorithm::DynamicProgramming),
+            2
+        );
+        assert_eq!(length(&template, &other, LcsAlgorithm::HuntSzymanski), 2);
+    }
+
+    #[test]
+    fn placeholders_never_match() {
+        let mut interner = Interner::new();
+        let template = template(&mut interner, "a * b");
+        let other = seq(&mut interner, "a * b");
+        for &algorithm in &ALGORITHMS {
+            assert_eq!(length(&template, &other, algorithm), 2);
+            assert_eq!(
+                alignment(&template, &other, algorithm),
+                vec![(0, 0), (2, 2)]
+            );
+        }
+
+        // A literal "*" is a constant like any other token
+        let template = seq(&mut interner, "a * b")
+            .into_iter()
+            .map(TemplateToken::Constant)
+            .collect::<Vec<_>>();
+        for &algorithm in &ALGORITHMS {
+            assert_eq!(length(&template, &other, algorithm), 3);
+        }
+    }
+
+    #[test]
+    fn optimal_algorithms_agree() {
+        let cases = [
+            ("", "a b"),
+            ("a b", ""),
+            ("a b c d", "a b c d"),
+            ("x a y b z", "a b"),
+            ("a a b a", "b a a a b"),
+            (
+                "Jan 22 * combo su(pam_unix)[21719]: session * for user news",
+                "Jan 22 05:23:19 combo sshd(pam_unix)[24892]: check pass; user unknown",
+            ),
+            ("1 2 3 4 1 2 3 4", "4 3 2 1 1 2 3 4 4"),
+        ];
+        let mut interner = Interner::new();
+        for &(left, right) in &cases {
+            let (template, other) = (template(&mut interner, left), seq(&mut interner, right));
+            let expected = length(&template, &other, LcsAlgorithm::DynamicProgramming);
+            for &algorithm in &ALGORITHMS {
+                let pairs = alignment(&template, &other, algorithm);
+                check_alignment(&template, &other, &pairs);
+                assert_eq!(pairs.len(), length(&template, &other, algorithm));
+                assert!(pairs.len() <= expected);
+                if algorithm != LcsAlgorithm::Greedy {
+                    assert_eq!(pairs.len(), expected);
+                }
+            }
+        }
+    }
+}
@@ -1,10 +1,32 @@
+pub mod concurrent;
+pub mod config;
+pub mod error;
+#[cfg(test)]
+mod fixtures;
+pub mod frozen;
+pub mod interner;
+mod inverted_index;
+pub mod lcs;
+pub mod line_ids;
+pub mod log_format;
 pub mod map;
+pub mod masking;
 pub mod object;
+pub mod observer;
+#[cfg(feature = "rayon")]
+pub mod parallel;
+pub mod persistence;
+mod prefix_tree;
+pub mod refine;
+pub mod template;
+pub mod tokenizer;
 
 pub type LineId = usize;
+pub type TemplateId = usize;
 
-pub type LcsToken = String;
+pub type LcsToken = interner::Symbol;
 pub type LcsSeq = Vec<LcsToken>;
+pub type LcsTemplate = Vec<template::TemplateToken>;
 pub type LcsDelimiters = Vec<char>;
 
 /// # Examples
@@ -14,11 +36,11 @@ pub type LcsDelimiters = Vec<char>;
 ///
 /// assert_eq!(vec!["a", "b", "c"], tokens);
 /// ```
-pub fn tokenize<'a>(input: &'a str, delimiters: &'a [char]) -> impl Iterator<Item = &'a str> {
-    input
-        .trim()
-        .split(&delimiters[..])
-        .filter(|s| !s.is_empty())
+pub fn tokenize<'a: 'b, 'b>(
+    input: &'a str,
+    delimiters: &'b [char],
+) -> impl Iterator<Item = &'a str> + 'b {
+    input.trim().split(delimiters).filter(|s| !s.is_empty())
 }
 
 #[cfg(test)]
@@ -0,0 +1,228 @@
+use std::collections::VecDeque;
+
+use roaring::RoaringTreemap;
+use serde_derive::*;
+
+use crate::LineId;
+
+/// How the identifiers of the lines belonging to a template are stored.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LineIdStorage {
+    /// Only the number of lines is kept.
+    Count,
+    /// All identifiers, in a compressed bitmap.
+    Bitmap,
+    /// Only the last `n` identifiers.
+    Recent(usize),
+    /// All identifiers, in insertion order.
+    #[default]
+    All,
+}
+
+/// Identifiers of the lines belonging to a template.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum LineIds {
+    Count(usize),
+    Bitmap(RoaringTreemap),
+    Recent {
+        capacity: usize,
+        ids: VecDeque<LineId>,
+        count: usize,
+    },
+    All(Vec<LineId>),
+}
+
+impl LineIds {
+    pub fn new(storage: LineIdStorage) -> LineIds {
+        match storage {
+            LineIdStorage::Count => LineIds::Count(0),
+            LineIdStorage::Bitmap => LineIds::Bitmap(RoaringTreemap::new()),
+            LineIdStorage::Recent(capacity) => LineIds::Recent {
+                capacity,
+                ids: VecDeque::with_capacity(capacity),
+                count: 0,
+            },
+            LineIdStorage::All => LineIds::All(Vec::new()),
+        }
+    }
+
+    pub fn storage(&self) -> LineIdStorage {
+        match self {
+            LineIds::Count(_) => LineIdStorage::Count,
+            LineIds::Bitmap(_) => LineIdStorage::Bitmap,
+            LineIds::Recent { capacity, .. } => LineIdStorage::Recent(*capacity),
+            LineIds::All(_) => LineIdStorage::All,
+        }
+    }
+
+    pub fn push(&mut self, line_id: LineId) {
+        match self {
+            LineIds::Count(count) => *count += 1,
+            LineIds::Bitmap(bitmap) => {
+                bitmap.insert(line_id as u64);
+            }
+            LineIds::Recent {
+                capacity,
+                ids,
+                count,
+            } => {
+                if ids.len() == *capacity {
+                    ids.pop_front();
+                }
+                if *capacity > 0 {
+                    ids.push_back(line_id);
+                }
+                *count += 1;
+            }
+            LineIds::All(ids) => ids.push(line_id),
+        }
+    }
+
+    /// Number of lines, including the ones whose identifier was not kept.
+    pub fn count(&self) -> usize {
+        match self {
+            LineIds::Count(count) => *count,
+            LineIds::Bitmap(bitmap) => bitmap.len() as usize,
+            LineIds::Recent { count, .. } => *count,
+            LineIds::All(ids) => ids.len(),
+        }
+    }
+
+    /// Number of identifiers kept.
+    pub fn len(&self) -> usize {
+        match self {
+            LineIds::Count(_) => 0,
+            LineIds::Bitmap(bitmap) => bitmap.len() as usize,
+            LineIds::Recent { ids, .. } => ids.len(),
+            LineIds::All(ids) => ids.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// `i`-th identifier kept.
+    pub fn get(&self, i: usize) -> Option<LineId> {
+        match self {
+            LineIds::Count(_) => None,
+            LineIds::Bitmap(bitmap) => bitmap.select(i as u64).map(|id| id as LineId),
+            LineIds::Recent { ids, .. } => ids.get(i).cloned(),
+            LineIds::All(ids) => ids.get(i).cloned(),
+        }
+    }
+
+    /// Identifiers kept.
+    pub fn iter(&self) -> Box<dyn Iterator<Item = LineId> + '_> {
+        match self {
+            LineIds::Count(_) => Box::new(std::iter::empty()),
+            LineIds::Bitmap(bitmap) => Box::new(bitmap.iter().map(|id| id as LineId)),
+            LineIds::Recent { ids, .. } => Box::new(ids.iter().cloned()),
+            LineIds::All(ids) => Box::new(ids.iter().cloned()),
+        }
+    }
+
+    /// Add the lines of `other`, shifting their identifiers by `offset`.
+    ///
+    /// Lines whose identifier was not kept by `other` are only counted if
+    /// this storage doesn't keep all the identifiers either. A bitmap counts
+    /// an identifier already present only once, which `LcsMap::merge()`
+    /// avoids by rejecting overlapping identifiers.
+    pub fn extend_from(&mut self, other: &LineIds, offset: LineId) {
+        for line_id in other.iter() {
+            self.push(line_id + offset);
+        }
+        let not_kept = other.count() - other.len();
+        match self {
+            LineIds::Count(count) | LineIds::Recent { count, .. } => *count += not_kept,
+            LineIds::Bitmap(_) | LineIds::All(_) => {}
+        }
+    }
+
+    pub fn to_vec(&self) -> Vec<LineId> {
+        self.iter().collect()
+    }
+}
+
+impl Default for LineIds {
+    fn default() -> LineIds {
+        LineIds::new(LineIdStorage::default())
+    }
+}
+
+impl From<Vec<LineId>> for LineIds {
+    fn from(ids: Vec<LineId>) -> LineIds {
+        LineIds::All(ids)
+    }
+}
+
+impl std::fmt::Display for LineIds {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if let LineIds::Count(count) = self {
+            return write!(f, "{} lines", count);
+        }
+        if self.len() < self.count() {
+            write!(f, "..., ")?;
+        }
+        write!(
+            f,
+            "{}",
+            self.iter()
+                .map(|i| i.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filled(storage: LineIdStorage) -> LineIds {
+        let mut ids = LineIds::new(storage);
+        (0..5).for_each(|i| ids.push(i));
+        ids
+    }
+
+    #[test]
+    fn storages() {
+        let ids = filled(LineIdStorage::All);
+        assert_eq!((ids.count(), ids.len()), (5, 5));
+        assert_eq!(ids.to_string(), "0, 1, 2, 3, 4");
+
+        let ids = filled(LineIdStorage::Bitmap);
+        assert_eq!((ids.count(), ids.len()), (5, 5));
+        assert_eq!(ids.get(3), Some(3));
+        assert_eq!(ids.to_string(), "0, 1, 2, 3, 4");
+
+        let ids = filled(LineIdStorage::Recent(2));
+        assert_eq!((ids.count(), ids.len()), (5, 2));
+        assert_eq!(ids.to_vec(), vec![3, 4]);
+        assert_eq!(ids.to_string(), "..., 3, 4");
+
+        let ids = filled(LineIdStorage::Recent(0));
+        assert_eq!((ids.count(), ids.len()), (5, 0));
+
+        let ids = filled(LineIdStorage::Count);
+        assert_eq!((ids.count(), ids.len()), (5, 0));
+        assert_eq!(ids.get(0), None);
+        assert_eq!(ids.to_string(), "5 lines");
+    }
+
+    #[test]
+    fn extend_from() {
+        let mut ids = filled(LineIdStorage::All);
+        ids.extend_from(&filled(LineIdStorage::Bitmap), 10);
+        assert_eq!(ids.to_vec(), vec![0, 1, 2, 3, 4, 10, 11, 12, 13, 14]);
+
+        let mut ids = filled(LineIdStorage::Recent(3));
+        ids.extend_from(&filled(LineIdStorage::Recent(2)), 5);
+        assert_eq!((ids.count(), ids.len()), (10, 3));
+        assert_eq!(ids.to_vec(), vec![4, 8, 9]);
+
+        let mut ids = filled(LineIdStorage::Count);
+        ids.extend_from(&filled(LineIdStorage::Count), 5);
+        assert_eq!(ids.count(), 10);
+    }
+}
@@ -0,0 +1,183 @@
+use std::convert::TryFrom;
+
+use regex::Regex;
+use serde_derive::*;
+
+use crate::error::{Error, Result};
+
+/// Name of the header holding the part of the line fed to Spell.
+pub const CONTENT: &str = "Content";
+
+/// Format of the log lines, used to split the headers from the content.
+///
+/// The specification uses the same syntax as LogPAI, for example
+/// `<Month> <Day> <Time> <Host> <Component>: <Content>`. Each `<Name>` is a
+/// header and everything else must appear literally, spaces matching any
+/// amount of whitespace. The `<Content>` header is required.
+///
+/// # Examples
+///
+/// ```
+/// let format = spell::log_format::LogFormat::new("<Date> <Level>: <Content>").unwrap();
+/// let (headers, content) = format.parse("2019-01-22 INFO: Server started").unwrap();
+///
+/// assert_eq!(headers, vec![("Date", "2019-01-22"), ("Level", "INFO")]);
+/// assert_eq!(content, "Server started");
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct LogFormat {
+    spec: String,
+    regex: Regex,
+    headers: Vec<String>,
+}
+
+impl LogFormat {
+    /// Compile a log format specification.
+    pub fn new(spec: &str) -> Result<LogFormat> {
+        let mut pattern = String::from("^");
+        let mut headers = Vec::new();
+
+        let mut rest = spec;
+        while let Some(start) = rest.find('<') {
+            let end = rest[start..]
+                .find('>')
+                .map(|end| start + end)
+                .ok_or_else(|| Error::LogFormat(format!("unclosed '<' in {:?}", spec)))?;
+            push_literal(&mut pattern, &rest[..start]);
+
+            let header = &rest[start + 1..end];
+            if header.is_empty() || header.contains('<') || headers.iter().any(|h| h == header) {
+                return Err(Error::LogFormat(format!(
+                    "invalid or duplicated header {:?}",
+                    header
+                )));
+            }
+            pattern.push_str("(.*?)");
+            headers.push(header.to_string());
+
+            rest = &rest[end + 1..];
+        }
+        push_literal(&mut pattern, rest);
+        pattern.push('$');
+
+        if !headers.iter().any(|h| h == CONTENT) {
+            return Err(Error::LogFormat(format!(
+                "missing <{}> in {:?}",
+                CONTENT, spec
+            )));
+        }
+
+        Ok(LogFormat {
+            spec: spec.to_string(),
+            regex: Regex::new(&pattern)?,
+            headers,
+        })
+    }
+
+    /// The specification the format was compiled from.
+    pub fn spec(&self) -> &str {
+        &self.spec
+    }
+
+    /// Names of the headers, in order, excluding the content.
+    pub fn headers(&self) -> impl Iterator<Item = &str> {
+        self.headers
+            .iter()
+            .map(|h| h.as_str())
+            .filter(|h| *h != CONTENT)
+    }
+
+    /// Split a line into its headers and its content.
+    ///
+    /// Returns `None` if the line does not follow the format.
+    pub fn parse<'a>(&self, line: &'a str) -> Option<(Vec<(&str, &'a str)>, &'a str)> {
+        let captures = self.regex.captures(line.trim())?;
+
+        let mut headers = Vec::with_capacity(self.headers.len() - 1);
+        let mut content = "";
+        for (name, value) in self.headers.iter().zip(captures.iter().skip(1)) {
+            let value = value.map(|m| m.as_str()).unwrap_or("");
+            if name == CONTENT {
+                content = value;
+            } else {
+                headers.push((name.as_str(), value.trim()));
+            }
+        }
+
+        Some((headers, content))
+    }
+}
+
+fn push_literal(pattern: &mut String, literal: &str) {
+    let parts: Vec<String> = literal.split(' ').map(regex::escape).collect();
+    pattern.push_str(&parts.join(r"\s+"));
+}
+
+impl PartialEq for LogFormat {
+    fn eq(&self, other: &LogFormat) -> bool {
+        self.spec == other.spec
+    }
+}
+
+impl TryFrom<String> for LogFormat {
+    type Error = Error;
+
+    fn try_from(spec: String) -> Result<LogFormat> {
+        LogFormat::new(&spec)
+    }
+}
+
+impl From<LogFormat> for String {
+    fn from(format: LogFormat) -> String {
+        format.spec
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYSLOG: &str = "<Month> <Day> <Time> <Host> <Component>: <Content>";
+
+    #[test]
+    fn parse_syslog() {
+        let format = LogFormat::new(SYSLOG).unwrap();
+        assert_eq!(
+            format.headers().collect::<Vec<_>>(),
+            vec!["Month", "Day", "Time", "Host", "Component"]
+        );
+
+        let line = include_str!("../fixtures/input/var_log_messages_line_3.txt");
+        let (headers, content) = format.parse(line).unwrap();
+        assert_eq!(
+            headers,
+            vec![
+                ("Month", "Jan"),
+                ("Day", "22"),
+                ("Time", "04:16:40"),
+                ("Host", "combo"),
+                ("Component", "su(pam_unix)[21719]"),
+            ]
+        );
+        assert_eq!(content, "session opened for user news by (uid=0)");
+
+        assert!(format.parse("not a syslog line").is_none());
+    }
+
+    #[test]
+    fn literals_are_escaped() {
+        let format = LogFormat::new("[<Level>] (<Thread>) <Content>").unwrap();
+        let (headers, content) = format.parse("[WARN]  (main) Disk almost full").unwrap();
+        assert_eq!(headers, vec![("Level", "WARN"), ("Thread", "main")]);
+        assert_eq!(content, "Disk almost full");
+    }
+
+    #[test]
+    fn invalid_specs() {
+        assert!(LogFormat::new("<Date> <Level>").is_err());
+        assert!(LogFormat::new("<Date <Content>").is_err());
+        assert!(LogFormat::new("<Date> <Date> <Content>").is_err());
+        assert!(LogFormat::new("<> <Content>").is_err());
+    }
+}
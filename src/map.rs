@@ -1,75 +1,554 @@
 use serde_derive::*;
 
-use crate::{object::LcsObject, tokenize, LcsDelimiters, LcsSeq, LineId};
+use crate::{
+    config::{CandidateSelection, SpellConfig},
+    error::{Error, Result},
+    interner::{Interner, Symbol},
+    inverted_index::InvertedIndex,
+    line_ids::LineIds,
+    log_format::LogFormat,
+    masking::{mask, MaskingRule},
+    object::LcsObject,
+    observer::{LcsObserver, Observers},
+    prefix_tree::PrefixTree,
+    template::{self, TemplateToken},
+    tokenizer::Tokenizer,
+    LcsDelimiters, LcsSeq, LcsTemplate, LcsToken, LineId, TemplateId,
+};
 
-#[derive(Default, Debug, PartialEq, Serialize, Deserialize)]
-pub struct LcsMap {
+/// Templates learned from the lines, split into tokens by `T`.
+#[derive(Default, Debug, Serialize, Deserialize)]
+pub struct LcsMap<T = LcsDelimiters> {
+    /// Templates, ordered by increasing `id`.
     pub seq: Vec<LcsObject>,
+    /// Strings of the tokens of the templates.
+    pub interner: Interner,
+    pub next_template_id: TemplateId,
     pub line_id: LineId,
-    pub delimiters: LcsDelimiters,
+    pub tokenizer: T,
+    #[serde(default)]
+    pub config: SpellConfig,
+    /// When set, only the content of the lines is fed to Spell.
+    #[serde(default)]
+    pub log_format: Option<LogFormat>,
+    /// Rules applied, in order, to the content of the lines before tokenization.
+    #[serde(default)]
+    pub masking: Vec<MaskingRule>,
+    #[serde(skip)]
+    prefix_tree: PrefixTree,
+    #[serde(skip)]
+    inverted_index: InvertedIndex,
+    #[serde(skip)]
+    pub(crate) observers: Observers,
 }
 
-macro_rules! fold_get_match {
-    ($iter:expr, $tokenized:expr) => {
-        $iter.fold((None, 0), |(best_match, best_match_length), obj| {
-            // Pruning as described in paper
-            if obj.length() < $tokenized.len() / 2 || obj.length() > $tokenized.len() * 2 {
-                (best_match, best_match_length)
-            } else {
-                let l = obj.get_lcs($tokenized);
-                if l >= $tokenized.len() / 2 && l > best_match_length {
-                    (Some(obj), l)
-                } else {
-                    (best_match, best_match_length)
-                }
-            }
-        })
-    };
+/// Identifiers given by `LcsMap::merge()` to the lines of the merged map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineOffset {
+    /// Lines are numbered after the lines of the map.
+    Next,
+    /// Line identifiers are shifted by this offset.
+    Offset(LineId),
+    /// Lines are numbered from `source * LINES_PER_SOURCE`, so that
+    /// `source_of()` tells the source of each line.
+    Source(usize),
+}
+
+impl LineOffset {
+    /// Identifiers available to the lines of each source.
+    pub const LINES_PER_SOURCE: LineId = 1_000_000_000;
+
+    /// Source of a line merged with `LineOffset::Source`. Lines inserted in
+    /// the map directly are from source 0.
+    pub fn source_of(line_id: LineId) -> usize {
+        line_id / LineOffset::LINES_PER_SOURCE
+    }
+
+    /// Identifier of the first line, `next` being the `line_id` of the map.
+    fn first_line_id(self, next: LineId) -> LineId {
+        match self {
+            LineOffset::Next => next,
+            LineOffset::Offset(offset) => offset,
+            LineOffset::Source(source) => source * LineOffset::LINES_PER_SOURCE,
+        }
+    }
+}
+
+/// Result of parsing a line with `LcsMap::parse()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedLine<'a> {
+    pub template_id: TemplateId,
+    /// Tokens of the line standing in for each placeholder of the template,
+    /// or masked by the masking rules.
+    pub params: Vec<Vec<&'a str>>,
+    /// Header fields split from the content by the map's `LogFormat`.
+    pub headers: Vec<(String, &'a str)>,
+}
+
+/// Best template found so far while looking up a line.
+#[derive(Debug, Clone, Copy)]
+struct Candidate {
+    index: usize,
+    length: usize,
+}
+
+impl Candidate {
+    /// Whether a template at `index` with an LCS of `length` is preferred over
+    /// this candidate. This mirrors a linear scan keeping the first longest match.
+    fn is_beaten_by(self, index: usize, length: usize) -> bool {
+        length > self.length || (length == self.length && index < self.index)
+    }
+}
+
+fn best_of(best: Option<Candidate>, other: Option<Candidate>) -> Option<Candidate> {
+    match (best, other) {
+        (Some(b), Some(o)) if b.is_beaten_by(o.index, o.length) => other,
+        (None, _) => other,
+        _ => best,
+    }
 }
 
 impl LcsMap {
     pub fn new() -> LcsMap {
-        LcsMap {
-            delimiters: vec![' '],
-            ..Default::default()
-        }
+        LcsMap::with_tokenizer(vec![' '])
     }
 
     /// Constructor to create an LcsMap with different set of delimiters.
     pub fn with_delimiters(delimiters: Vec<char>) -> LcsMap {
+        LcsMap::with_tokenizer(delimiters)
+    }
+
+    /// Constructor to create an LcsMap with custom matching parameters.
+    pub fn with_config(config: SpellConfig) -> LcsMap {
         LcsMap {
-            delimiters,
+            config,
             ..LcsMap::new()
         }
     }
 
+    /// Constructor to create an LcsMap splitting the headers from the content
+    /// of the lines.
+    pub fn with_log_format(log_format: LogFormat) -> LcsMap {
+        LcsMap {
+            log_format: Some(log_format),
+            ..LcsMap::new()
+        }
+    }
+
+    /// Constructor to create an LcsMap masking variables before tokenization.
+    pub fn with_masking(masking: Vec<MaskingRule>) -> LcsMap {
+        LcsMap {
+            masking,
+            ..LcsMap::new()
+        }
+    }
+}
+
+impl<T: Tokenizer> LcsMap<T> {
+    /// Constructor to create an LcsMap splitting the lines with a custom
+    /// tokenizer.
+    pub fn with_tokenizer(tokenizer: T) -> LcsMap<T> {
+        LcsMap {
+            seq: Vec::new(),
+            interner: Interner::new(),
+            next_template_id: 0,
+            line_id: 0,
+            tokenizer,
+            config: SpellConfig::default(),
+            log_format: None,
+            masking: Vec::new(),
+            prefix_tree: PrefixTree::new(),
+            inverted_index: InvertedIndex::new(),
+            observers: Observers::default(),
+        }
+    }
+
     pub fn insert(&mut self, entry: &str) {
-        let tokenized: LcsSeq = tokenize(entry, self.delimiters.as_slice())
-            .map(|token| token.to_string())
+        self.insert_entry(entry);
+    }
+
+    /// Same as `insert()`, returning the identifier of the template the line
+    /// was added to.
+    pub fn insert_returning_id(&mut self, entry: &str) -> TemplateId {
+        let index = self.insert_entry(entry);
+        self.seq[index].id
+    }
+
+    /// Same as `insert()`, returning the index of the template the line was
+    /// added to.
+    pub(crate) fn insert_entry(&mut self, entry: &str) -> usize {
+        let (_headers, entry) = self.split_headers(entry);
+        let masked = mask(&self.masking, entry);
+        let tokens = self.tokenizer.tokenize(masked.text());
+        self.insert_tokens(&tokens)
+    }
+
+    /// Identifier of the template matching the line, or `None` if the line
+    /// is unknown, without inserting the line.
+    ///
+    /// The line goes through the same log format, masking and tokenizer as
+    /// when inserted, and is matched with the same criteria.
+    pub fn classify(&self, entry: &str) -> Option<TemplateId> {
+        let (_headers, entry) = self.split_headers(entry);
+        let masked = mask(&self.masking, entry);
+        let tokens = self.tokenizer.tokenize(masked.text());
+        let tokenized = self.interner.lookup(&tokens);
+        self.get_match(&tokenized).map(|obj| obj.id)
+    }
+
+    /// Insert a line and extract the values of its template's placeholders.
+    ///
+    /// The parameters are extracted from the template after it was
+    /// generalized to accommodate the line. Values replaced by the masking
+    /// rules are parameters too, and are returned unmasked.
+    pub fn parse<'a>(&mut self, entry: &'a str) -> ParsedLine<'a> {
+        let (headers, entry) = self.split_headers(entry);
+        let headers = headers
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect();
+        let masked = mask(&self.masking, entry);
+        let tokens = self.tokenizer.tokenize(masked.text());
+        let index = self.insert_tokens(&tokens);
+        let obj = &self.seq[index];
+        let params = obj
+            .extract_parameters_with(&tokens, &self.interner, |token| masked.is_masked(token))
+            .into_iter()
+            .map(|tokens| {
+                tokens
+                    .into_iter()
+                    .map(|token| masked.original(token))
+                    .collect()
+            })
             .collect();
+        ParsedLine {
+            template_id: obj.id,
+            params,
+            headers,
+        }
+    }
 
+    /// Header fields and content of a line. Lines not following the log
+    /// format are used as is.
+    pub(crate) fn split_headers<'a>(&self, entry: &'a str) -> (Vec<(&str, &'a str)>, &'a str) {
+        self.log_format
+            .as_ref()
+            .and_then(|log_format| log_format.parse(entry))
+            .unwrap_or((Vec::new(), entry))
+    }
+
+    /// Returns the index of the template the line was added to.
+    fn insert_tokens(&mut self, tokens: &[&str]) -> usize {
         let line_id = self.line_id;
 
-        match self.get_match_mut(&tokenized) {
+        if !self.is_index_synced() {
+            self.rebuild_index();
+        }
+
+        // Tokens are only interned when they become part of a template
+        let tokenized = self.interner.lookup(tokens);
+        let index = match self.get_match_index(&tokenized) {
             None => {
-                let obj = LcsObject::new(tokenized, line_id);
-                self.seq.push(obj);
+                let tokenized = tokens.iter().map(|s| self.interner.intern(s)).collect();
+                let obj = LcsObject::with_config(tokenized, line_id, &self.config);
+                self.add_template(obj)
             }
-            Some(obj) => {
-                obj.insert(tokenized, line_id);
+            Some(index) => {
+                let algorithm = self.config.algorithm;
+                let updated = self.seq[index].insert_symbols(
+                    &tokenized,
+                    tokens,
+                    line_id,
+                    &self.interner,
+                    algorithm,
+                );
+                if let Some(previous) = updated {
+                    self.template_updated(index, &previous);
+                }
+                for observer in &mut self.observers.0 {
+                    observer.on_line_matched(&self.seq[index], line_id);
+                }
+                index
             }
-        }
+        };
         self.line_id += 1;
+        index
+    }
+
+    /// Add the templates of `other`, learned from other lines, for example on
+    /// another host, numbering its lines as set by `offset`.
+    ///
+    /// Each template of `other` is matched against the templates of this map
+    /// like a line would be, using this map's `config`. It generalizes the
+    /// template it matches, or is added as a new template. Returns the
+    /// identifier of the template each template of `other` ended up in, in
+    /// the order of `other.seq`. The observers of this map are notified of the
+    /// templates created or generalized.
+    ///
+    /// Lines can't share an identifier, so an offset numbering the lines of
+    /// `other` before the `line_id` of this map is an error, and the map is
+    /// left untouched. Sources must therefore be merged in increasing order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use spell::map::{LcsMap, LineOffset};
+    ///
+    /// let mut map = LcsMap::new();
+    ///
+    /// let mut host = LcsMap::new();
+    /// host.insert("Command Failed on: node-127");
+    /// assert_eq!(map.merge(host, LineOffset::Source(1)).unwrap(), vec![0]);
+    ///
+    /// let mut host = LcsMap::new();
+    /// host.insert("Server started");
+    /// host.insert("Command Failed on: node-234");
+    /// assert_eq!(map.merge(host, LineOffset::Source(2)).unwrap(), vec![1, 0]);
+    ///
+    /// assert_eq!(map.seq[0].template(&map.interner), vec!["Command", "Failed", "on:", "*"]);
+    /// let sources: Vec<_> = map.seq[0].lines_ids.iter().map(LineOffset::source_of).collect();
+    /// assert_eq!(sources, vec![1, 2]);
+    ///
+    /// // Source 1 comes before the lines already merged
+    /// let host = LcsMap::new();
+    /// assert!(map.merge(host, LineOffset::Source(1)).is_err());
+    /// ```
+    pub fn merge<U>(&mut self, other: LcsMap<U>, offset: LineOffset) -> Result<Vec<TemplateId>> {
+        let line_offset = offset.first_line_id(self.line_id);
+        if line_offset < self.line_id {
+            return Err(Error::OverlappingLines(line_offset, self.line_id));
+        }
+
+        if !self.is_index_synced() {
+            self.rebuild_index();
+        }
+
+        let mut ids = Vec::with_capacity(other.seq.len());
+        for obj in &other.seq {
+            // Wildcards don't match anything, like tokens never seen before
+            let symbols: LcsSeq = obj
+                .tokens
+                .iter()
+                .map(|token| {
+                    token
+                        .constant()
+                        .and_then(|symbol| self.interner.get(other.interner.resolve(symbol)))
+                        .unwrap_or(Symbol::UNKNOWN)
+                })
+                .collect();
+            let index = match self.get_match_index(&symbols) {
+                None => {
+                    let tokens = obj
+                        .tokens
+                        .iter()
+                        .map(|token| match *token {
+                            TemplateToken::Constant(symbol) => TemplateToken::Constant(
+                                self.interner.intern(other.interner.resolve(symbol)),
+                            ),
+                            wildcard => wildcard,
+                        })
+                        .collect();
+                    let mut lines_ids = LineIds::new(self.config.line_ids);
+                    lines_ids.extend_from(&obj.lines_ids, line_offset);
+                    let params = obj.params.clone().map(|mut params| {
+                        params.shift(line_offset);
+                        params
+                    });
+                    self.add_template(LcsObject {
+                        id: 0,
+                        tokens,
+                        lines_ids,
+                        params,
+                    })
+                }
+                Some(index) => {
+                    let updated = self.seq[index].absorb(
+                        obj,
+                        &symbols,
+                        &other.interner,
+                        &self.interner,
+                        line_offset,
+                        self.config.algorithm,
+                    );
+                    if let Some(previous) = updated {
+                        self.template_updated(index, &previous);
+                    }
+                    index
+                }
+            };
+            ids.push(self.seq[index].id);
+        }
+        self.line_id = self.line_id.max(line_offset + other.line_id);
+        Ok(ids)
+    }
+
+    /// Give a new template its identifier and add it, returning its index.
+    pub(crate) fn add_template(&mut self, mut obj: LcsObject) -> usize {
+        obj.id = self.next_template_id;
+        self.next_template_id += 1;
+        self.seq.push(obj);
+        let index = self.seq.len() - 1;
+        self.index_insert(index);
+        for observer in &mut self.observers.0 {
+            observer.on_new_template(&self.seq[index], &self.interner);
+        }
+        index
+    }
+
+    /// Update the lookup structures after the template at `index` changed.
+    fn template_updated(&mut self, index: usize, previous: &LcsTemplate) {
+        let constants = template::constants(previous);
+        self.prefix_tree.remove(index, constants.clone());
+        self.inverted_index.remove(index, constants);
+        self.index_insert(index);
+        for observer in &mut self.observers.0 {
+            observer.on_template_updated(previous, &self.seq[index], &self.interner);
+        }
+    }
+
+    /// Register an observer notified of the changes made to the templates.
+    pub fn add_observer<O: LcsObserver + 'static>(&mut self, observer: O) {
+        self.observers.0.push(Box::new(observer));
+    }
+
+    /// Template with the given identifier.
+    pub fn get(&self, id: TemplateId) -> Option<&LcsObject> {
+        self.position(id).map(|index| &self.seq[index])
+    }
+
+    pub fn get_mut(&mut self, id: TemplateId) -> Option<&mut LcsObject> {
+        self.position(id).map(move |index| &mut self.seq[index])
+    }
+
+    fn position(&self, id: TemplateId) -> Option<usize> {
+        self.seq.binary_search_by_key(&id, |obj| obj.id).ok()
     }
 
     pub fn get_match(&self, tokenized: &LcsSeq) -> Option<&LcsObject> {
-        let (best_match, _best_match_length) = fold_get_match!(self.seq.iter(), tokenized);
-        best_match
+        self.get_match_index(tokenized)
+            .map(|index| &self.seq[index])
     }
 
     pub fn get_match_mut(&mut self, tokenized: &LcsSeq) -> Option<&mut LcsObject> {
-        let (best_match, _best_match_length) = fold_get_match!(self.seq.iter_mut(), tokenized);
-        best_match
+        self.get_match_index(tokenized)
+            .map(move |index| &mut self.seq[index])
+    }
+
+    /// Rebuild the lookup structures from `seq`.
+    ///
+    /// This is done automatically on the next insertion if templates were
+    /// added or removed, but must be called after modifying the tokens of
+    /// the objects in `seq` directly.
+    pub fn rebuild_index(&mut self) {
+        self.prefix_tree = PrefixTree::new();
+        self.inverted_index = InvertedIndex::new();
+        for index in 0..self.seq.len() {
+            self.index_insert(index);
+        }
+    }
+
+    fn index_insert(&mut self, index: usize) {
+        let obj = &self.seq[index];
+        self.prefix_tree.insert(index, obj.constants());
+        self.inverted_index.insert(index, obj.constants());
+    }
+
+    fn is_index_synced(&self) -> bool {
+        self.prefix_tree.len() == self.seq.len() && self.inverted_index.len() == self.seq.len()
+    }
+
+    /// Index in `seq` of the template with the longest LCS, the first one
+    /// winning ties.
+    ///
+    /// As described in the paper, the prefix tree and then a simple loop are
+    /// tried first. These find templates whose constants all appear in the
+    /// line, which bounds the LCS a better template must reach. The full scan
+    /// then only computes the LCS of the templates that could still win.
+    fn get_match_index(&self, tokenized: &LcsSeq) -> Option<usize> {
+        let mut best = self.lookup_prefix_tree(tokenized);
+        if best.is_none() {
+            best = self.lookup_simple_loop(tokenized);
+        }
+
+        for (index, upper_bound) in self.candidates(tokenized) {
+            if let Some(best) = best {
+                if !best.is_beaten_by(index, upper_bound) {
+                    continue;
+                }
+            }
+            let obj = &self.seq[index];
+            if let Some(length) = self.accepted_lcs(
+                obj,
+                tokenized,
+                obj.get_lcs_with(tokenized, self.config.algorithm),
+            ) {
+                best = best_of(best, Some(Candidate { index, length }));
+            }
+        }
+
+        best.map(|best| best.index)
+    }
+
+    /// Templates to run the full LCS on, along with an upper bound of their LCS.
+    fn candidates<'a>(
+        &'a self,
+        tokenized: &[LcsToken],
+    ) -> Box<dyn Iterator<Item = (usize, usize)> + 'a> {
+        match self.config.candidates {
+            CandidateSelection::SharedTokens { top_k } if self.is_index_synced() => {
+                let candidates = self.inverted_index.candidates(tokenized).into_iter();
+                match top_k {
+                    Some(top_k) => Box::new(candidates.take(top_k)),
+                    None => Box::new(candidates),
+                }
+            }
+            // The LCS can't be longer than the number of constant tokens
+            _ => Box::new(
+                self.seq
+                    .iter()
+                    .map(|obj| obj.constants().count())
+                    .enumerate(),
+            ),
+        }
+    }
+
+    fn lookup_prefix_tree(&self, tokenized: &LcsSeq) -> Option<Candidate> {
+        if !self.is_index_synced() {
+            return None;
+        }
+        self.prefix_tree
+            .lookup(tokenized)
+            .filter_map(|(index, length)| {
+                let obj = self.seq.get(index)?;
+                // Make sure the tree is not stale
+                if !obj.is_subsequence_of(tokenized) {
+                    return None;
+                }
+                self.accepted_lcs(obj, tokenized, length)
+                    .map(|length| Candidate { index, length })
+            })
+            .fold(None, |best, candidate| best_of(best, Some(candidate)))
+    }
+
+    fn lookup_simple_loop(&self, tokenized: &LcsSeq) -> Option<Candidate> {
+        let threshold = self.config.threshold(tokenized.len());
+        self.seq
+            .iter()
+            .enumerate()
+            .filter_map(|(index, obj)| {
+                let length = obj.constants().count();
+                if length < threshold || !obj.is_subsequence_of(tokenized) {
+                    return None;
+                }
+                self.accepted_lcs(obj, tokenized, length)
+                    .map(|length| Candidate { index, length })
+            })
+            .fold(None, |best, candidate| best_of(best, Some(candidate)))
+    }
+
+    fn accepted_lcs(&self, obj: &LcsObject, tokenized: &LcsSeq, length: usize) -> Option<usize> {
+        self.config
+            .accepted_lcs(obj.length(), tokenized.len(), length)
     }
 
     // fn object_at(&self, index: usize) -> Option<&LcsObject> {
@@ -81,13 +560,45 @@ impl LcsMap {
     }
 }
 
-impl std::fmt::Display for LcsMap {
+impl<T: PartialEq> PartialEq for LcsMap<T> {
+    fn eq(&self, other: &LcsMap<T>) -> bool {
+        // The lookup structures are derived from `seq` and are not compared.
+        // Symbols depend on the order the tokens were interned in, so the
+        // constants are compared through their strings.
+        self.seq.len() == other.seq.len()
+            && self.seq.iter().zip(&other.seq).all(|(left, right)| {
+                left.id == right.id
+                    && left.lines_ids == right.lines_ids
+                    && left.params == right.params
+                    && left.tokens.len() == right.tokens.len()
+                    && left
+                        .tokens
+                        .iter()
+                        .zip(&right.tokens)
+                        .all(|(l, r)| match (l, r) {
+                            (TemplateToken::Constant(l), TemplateToken::Constant(r)) => {
+                                self.interner.resolve(*l) == other.interner.resolve(*r)
+                            }
+                            (TemplateToken::Wildcard(l), TemplateToken::Wildcard(r)) => l == r,
+                            _ => false,
+                        })
+            })
+            && self.next_template_id == other.next_template_id
+            && self.line_id == other.line_id
+            && self.tokenizer == other.tokenizer
+            && self.config == other.config
+            && self.log_format == other.log_format
+            && self.masking == other.masking
+    }
+}
+
+impl<T: Tokenizer> std::fmt::Display for LcsMap<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(f, "\t{} Objects in the LCSMap\n\n", self.size())?;
 
         let mut entry_count = 0;
         for (i, obj) in self.seq.iter().enumerate() {
-            write!(f, "\tObject {}:\n\t\t{}\n", i, obj.to_string())?;
+            write!(f, "\tObject {}:\n\t\t{}\n", i, obj.display(&self.interner))?;
             entry_count += obj.count();
         }
 
@@ -102,16 +613,48 @@ impl std::fmt::Display for LcsMap {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        fixtures::{var_log_messages_lines, Recorder},
+        lcs::LcsAlgorithm,
+        line_ids::LineIdStorage,
+        template::ParamType,
+        tokenize,
+    };
 
-    fn fixtures_input_var_log_messages_lines() -> [&'static str; 6] {
-        [
-            include_str!("../fixtures/input/var_log_messages_line_1.txt"),
-            include_str!("../fixtures/input/var_log_messages_line_2.txt"),
-            include_str!("../fixtures/input/var_log_messages_line_3.txt"),
-            include_str!("../fixtures/input/var_log_messages_line_4.txt"),
-            include_str!("../fixtures/input/var_log_messages_line_5.txt"),
-            include_str!("../fixtures/input/var_log_messages_line_6.txt"),
-        ]
+    type Template = (TemplateId, Vec<&'static str>, Vec<LineId>);
+
+    fn object(id: TemplateId, tokens: &[&'static str], lines_ids: Vec<LineId>) -> Template {
+        (id, tokens.to_vec(), lines_ids)
+    }
+
+    fn expected_map(
+        templates: Vec<Template>,
+        next_template_id: TemplateId,
+        line_id: LineId,
+    ) -> LcsMap {
+        let mut map = LcsMap::new();
+        for (id, tokens, lines_ids) in templates {
+            let tokens = tokens
+                .iter()
+                .map(|s| match *s {
+                    "*" => TemplateToken::wildcard(),
+                    "<TEXT>" => TemplateToken::Wildcard(ParamType::Text.into()),
+                    _ => TemplateToken::Constant(map.interner.intern(s)),
+                })
+                .collect();
+            let obj = LcsObject {
+                id,
+                tokens,
+                lines_ids: lines_ids.into(),
+                params: None,
+            };
+            map.seq.push(obj);
+        }
+        LcsMap {
+            next_template_id,
+            line_id,
+            ..map
+        }
     }
 
     fn fixtures_output_original_impl() -> [&'static str; 6] {
@@ -127,7 +670,7 @@ mod tests {
 
     #[test]
     fn compare_java() {
-        let inputs = fixtures_input_var_log_messages_lines();
+        let inputs = var_log_messages_lines();
         let expected = fixtures_output_original_impl();
 
         let mut map = LcsMap::new();
@@ -159,46 +702,39 @@ mod tests {
 
     #[test]
     fn parse_log() {
-        let inputs = fixtures_input_var_log_messages_lines();
+        let inputs = var_log_messages_lines();
 
         let mut map = LcsMap::new();
-        let expected = LcsMap {
-            seq: Vec::new(),
-            line_id: 0,
-            delimiters: vec![' '],
-        };
+        let expected = LcsMap::new();
         assert_eq!(map, expected);
 
         map.insert(inputs[0]);
-        let expected = LcsMap {
-            seq: vec![LcsObject {
-                tokens: [
+        let expected = expected_map(
+            vec![object(
+                0,
+                &[
                     "Jan", "22", "04:11:04", "combo", "syslogd", "1.4.1:", "restart.",
-                ]
-                .iter()
-                .map(|s| s.to_string())
-                .collect(),
-                lines_ids: vec![0],
-            }],
-            line_id: 1,
-            delimiters: vec![' '],
-        };
+                ],
+                vec![0],
+            )],
+            1,
+            1,
+        );
         assert_eq!(map, expected);
 
         map.insert(inputs[1]);
-        let expected = LcsMap {
-            seq: vec![
-                LcsObject {
-                    tokens: [
+        let expected = expected_map(
+            vec![
+                object(
+                    0,
+                    &[
                         "Jan", "22", "04:11:04", "combo", "syslogd", "1.4.1:", "restart.",
-                    ]
-                    .iter()
-                    .map(|s| s.to_string())
-                    .collect(),
-                    lines_ids: vec![0],
-                },
-                LcsObject {
-                    tokens: [
+                    ],
+                    vec![0],
+                ),
+                object(
+                    1,
+                    &[
                         "Jan",
                         "22",
                         "04:11:04",
@@ -209,32 +745,28 @@ mod tests {
                         "abnormally",
                         "with",
                         "[1]",
-                    ]
-                    .iter()
-                    .map(|s| s.to_string())
-                    .collect(),
-                    lines_ids: vec![1],
-                },
+                    ],
+                    vec![1],
+                ),
             ],
-            line_id: 2,
-            delimiters: vec![' '],
-        };
+            2,
+            2,
+        );
         assert_eq!(map, expected);
 
         map.insert(inputs[2]);
-        let expected = LcsMap {
-            seq: vec![
-                LcsObject {
-                    tokens: [
+        let expected = expected_map(
+            vec![
+                object(
+                    0,
+                    &[
                         "Jan", "22", "04:11:04", "combo", "syslogd", "1.4.1:", "restart.",
-                    ]
-                    .iter()
-                    .map(|s| s.to_string())
-                    .collect(),
-                    lines_ids: vec![0],
-                },
-                LcsObject {
-                    tokens: [
+                    ],
+                    vec![0],
+                ),
+                object(
+                    1,
+                    &[
                         "Jan",
                         "22",
                         "04:11:04",
@@ -245,14 +777,12 @@ mod tests {
                         "abnormally",
                         "with",
                         "[1]",
-                    ]
-                    .iter()
-                    .map(|s| s.to_string())
-                    .collect(),
-                    lines_ids: vec![1],
-                },
-                LcsObject {
-                    tokens: [
+                    ],
+                    vec![1],
+                ),
+                object(
+                    2,
+                    &[
                         "Jan",
                         "22",
                         "04:16:40",
@@ -265,32 +795,28 @@ mod tests {
                         "news",
                         "by",
                         "(uid=0)",
-                    ]
-                    .iter()
-                    .map(|s| s.to_string())
-                    .collect(),
-                    lines_ids: vec![2],
-                },
+                    ],
+                    vec![2],
+                ),
             ],
-            line_id: 3,
-            delimiters: vec![' '],
-        };
+            3,
+            3,
+        );
         assert_eq!(map, expected);
 
         map.insert(inputs[3]);
-        let expected = LcsMap {
-            seq: vec![
-                LcsObject {
-                    tokens: [
+        let expected = expected_map(
+            vec![
+                object(
+                    0,
+                    &[
                         "Jan", "22", "04:11:04", "combo", "syslogd", "1.4.1:", "restart.",
-                    ]
-                    .iter()
-                    .map(|s| s.to_string())
-                    .collect(),
-                    lines_ids: vec![0],
-                },
-                LcsObject {
-                    tokens: [
+                    ],
+                    vec![0],
+                ),
+                object(
+                    1,
+                    &[
                         "Jan",
                         "22",
                         "04:11:04",
@@ -301,50 +827,44 @@ mod tests {
                         "abnormally",
                         "with",
                         "[1]",
-                    ]
-                    .iter()
-                    .map(|s| s.to_string())
-                    .collect(),
-                    lines_ids: vec![1],
-                },
-                LcsObject {
-                    tokens: [
+                    ],
+                    vec![1],
+                ),
+                object(
+                    2,
+                    &[
                         "Jan",
                         "22",
-                        "*",
+                        "<TEXT>",
                         "combo",
                         "su(pam_unix)[21719]:",
                         "session",
-                        "*",
+                        "<TEXT>",
                         "for",
                         "user",
                         "news",
-                    ]
-                    .iter()
-                    .map(|s| s.to_string())
-                    .collect(),
-                    lines_ids: vec![2, 3],
-                },
+                    ],
+                    vec![2, 3],
+                ),
             ],
-            line_id: 4,
-            delimiters: vec![' '],
-        };
+            3,
+            4,
+        );
         assert_eq!(map, expected);
 
         map.insert(inputs[4]);
-        let expected = LcsMap {
-            seq: vec![
-                LcsObject {
-                    tokens: [
+        let expected = expected_map(
+            vec![
+                object(
+                    0,
+                    &[
                         "Jan", "22", "04:11:04", "combo", "syslogd", "1.4.1:", "restart.",
-                    ]
-                    .iter()
-                    .map(|s| s.to_string())
-                    .collect(),
-                    lines_ids: vec![0],
-                },
-                LcsObject {
-                    tokens: [
+                    ],
+                    vec![0],
+                ),
+                object(
+                    1,
+                    &[
                         "Jan",
                         "22",
                         "04:11:04",
@@ -355,39 +875,33 @@ mod tests {
                         "abnormally",
                         "with",
                         "[1]",
-                    ]
-                    .iter()
-                    .map(|s| s.to_string())
-                    .collect(),
-                    lines_ids: vec![1],
-                },
-                LcsObject {
-                    tokens: ["Jan", "22", "*", "combo", "*", "user", "*"]
-                        .iter()
-                        .map(|s| s.to_string())
-                        .collect(),
-                    lines_ids: vec![2, 3, 4],
-                },
+                    ],
+                    vec![1],
+                ),
+                object(
+                    2,
+                    &["Jan", "22", "<TEXT>", "combo", "<TEXT>", "user", "<TEXT>"],
+                    vec![2, 3, 4],
+                ),
             ],
-            line_id: 5,
-            delimiters: vec![' '],
-        };
+            3,
+            5,
+        );
         assert_eq!(map, expected);
 
         map.insert(inputs[5]);
-        let expected = LcsMap {
-            seq: vec![
-                LcsObject {
-                    tokens: [
+        let expected = expected_map(
+            vec![
+                object(
+                    0,
+                    &[
                         "Jan", "22", "04:11:04", "combo", "syslogd", "1.4.1:", "restart.",
-                    ]
-                    .iter()
-                    .map(|s| s.to_string())
-                    .collect(),
-                    lines_ids: vec![0],
-                },
-                LcsObject {
-                    tokens: [
+                    ],
+                    vec![0],
+                ),
+                object(
+                    1,
+                    &[
                         "Jan",
                         "22",
                         "04:11:04",
@@ -398,21 +912,17 @@ mod tests {
                         "abnormally",
                         "with",
                         "[1]",
-                    ]
-                    .iter()
-                    .map(|s| s.to_string())
-                    .collect(),
-                    lines_ids: vec![1],
-                },
-                LcsObject {
-                    tokens: ["Jan", "22", "*", "combo", "*", "user", "*"]
-                        .iter()
-                        .map(|s| s.to_string())
-                        .collect(),
-                    lines_ids: vec![2, 3, 4],
-                },
-                LcsObject {
-                    tokens: [
+                    ],
+                    vec![1],
+                ),
+                object(
+                    2,
+                    &["Jan", "22", "<TEXT>", "combo", "<TEXT>", "user", "<TEXT>"],
+                    vec![2, 3, 4],
+                ),
+                object(
+                    3,
+                    &[
                         "Jan",
                         "22",
                         "05:23:19",
@@ -426,45 +936,508 @@ mod tests {
                         "tty=NODEVssh",
                         "ruser=",
                         "rhost=server3.sugolan.hu",
-                    ]
-                    .iter()
-                    .map(|s| s.to_string())
-                    .collect(),
-                    lines_ids: vec![5],
-                },
+                    ],
+                    vec![5],
+                ),
             ],
-            line_id: 6,
-            delimiters: vec![' '],
-        };
+            4,
+            6,
+        );
         assert_eq!(map, expected);
     }
 
+    /// Lines built from a small vocabulary so that templates get merged
+    fn generated_lines(count: usize) -> Vec<String> {
+        let vocabulary = [
+            "Jan", "22", "combo", "session", "opened", "closed", "for", "user", "news", "root",
+            "by", "(uid=0)", "failure;", "*",
+        ];
+        let mut state: u64 = 42;
+        let mut next = move || {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1);
+            (state >> 33) as usize
+        };
+        (0..count)
+            .map(|_| {
+                let length = 1 + next() % 10;
+                (0..length)
+                    .map(|_| vocabulary[next() % vocabulary.len()])
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect()
+    }
+
+    /// Linear scan over all templates, as originally implemented
+    fn full_scan(map: &LcsMap, tokenized: &LcsSeq) -> Option<usize> {
+        let mut best = None;
+        let mut best_length = 0;
+        for (index, obj) in map.seq.iter().enumerate() {
+            if map.config.is_pruned(obj.length(), tokenized.len()) {
+                continue;
+            }
+            let l = obj.get_lcs_with(tokenized, map.config.algorithm);
+            if l >= map.config.threshold(tokenized.len()) && l > best_length {
+                best = Some(index);
+                best_length = l;
+            }
+        }
+        best
+    }
+
+    #[test]
+    fn lookup_stages_match_full_scan() {
+        let shared_tokens = CandidateSelection::SharedTokens { top_k: None };
+        for config in &[
+            SpellConfig::default(),
+            SpellConfig::default().with_tau(0.3).without_pruning(),
+            SpellConfig::default().with_candidates(shared_tokens),
+            SpellConfig::default()
+                .with_tau(0.3)
+                .without_pruning()
+                .with_candidates(shared_tokens),
+            SpellConfig::default().with_algorithm(LcsAlgorithm::DynamicProgramming),
+            SpellConfig::default()
+                .with_algorithm(LcsAlgorithm::HuntSzymanski)
+                .with_candidates(shared_tokens),
+        ] {
+            let mut map = LcsMap::with_config(config.clone());
+            for line in generated_lines(500) {
+                let tokens: Vec<_> = tokenize(&line, &[' ']).collect();
+                let tokenized = map.interner.lookup(&tokens);
+                assert_eq!(map.get_match_index(&tokenized), full_scan(&map, &tokenized));
+                map.insert(&line);
+            }
+            assert!(map.seq.len() > 1);
+        }
+    }
+
+    #[test]
+    fn inverted_index_follows_generalization() {
+        let inputs = var_log_messages_lines();
+
+        let config = SpellConfig::default()
+            .with_candidates(CandidateSelection::SharedTokens { top_k: None });
+        let mut map = LcsMap::with_config(config);
+        inputs.iter().for_each(|input| map.insert(input));
+        assert_eq!(map.to_string(), fixtures_output_original_impl()[5]);
+
+        let tokenized = |s: &str| -> LcsSeq {
+            let tokens: Vec<_> = tokenize(s, &[' ']).collect();
+            map.interner.lookup(&tokens)
+        };
+        // "session" was turned into a "*" in object 2
+        let candidates = map.inverted_index.candidates(&tokenized("session opened"));
+        assert!(candidates.is_empty());
+        let candidates = map
+            .inverted_index
+            .candidates(&tokenized("Jan 22 combo user"));
+        assert_eq!(candidates[0], (2, 4));
+
+        let config = SpellConfig::default()
+            .with_candidates(CandidateSelection::SharedTokens { top_k: Some(1) });
+        let mut map = LcsMap::with_config(config);
+        inputs.iter().for_each(|input| map.insert(input));
+        assert_eq!(map.seq[2].count(), 3);
+    }
+
+    #[test]
+    fn parse_extracts_parameters() {
+        let inputs = var_log_messages_lines();
+
+        let mut map = LcsMap::new();
+        let parsed: Vec<_> = inputs.iter().map(|input| map.parse(input)).collect();
+
+        let ids: Vec<_> = parsed.iter().map(|parsed| parsed.template_id).collect();
+        assert_eq!(ids, vec![0, 1, 2, 2, 2, 3]);
+        assert!(parsed[0].params.is_empty());
+        assert_eq!(parsed[3].params, vec![vec!["04:16:41"], vec!["closed"]]);
+        assert_eq!(
+            parsed[4].params,
+            vec![
+                vec!["05:23:19"],
+                vec!["sshd(pam_unix)[24892]:", "check", "pass;"],
+                vec!["unknown"],
+            ]
+        );
+    }
+
+    #[test]
+    fn only_template_tokens_are_interned() {
+        let mut map = LcsMap::new();
+        var_log_messages_lines()
+            .iter()
+            .for_each(|input| map.insert(input));
+
+        // Line 4 matched an existing template
+        assert!(map.interner.get("04:16:41").is_none());
+        assert!(map.interner.get("04:16:40").is_some());
+    }
+
+    #[test]
+    fn template_ids_are_stable() {
+        let inputs = var_log_messages_lines();
+
+        let mut map = LcsMap::new();
+        let parsed: Vec<_> = inputs[..3].iter().map(|input| map.parse(input)).collect();
+        let id = parsed[2].template_id;
+        let before = map.get(id).unwrap().tokens.clone();
+
+        // Template gets generalized but keeps its identifier
+        assert_eq!(map.parse(inputs[3]).template_id, id);
+        assert_eq!(map.parse(inputs[4]).template_id, id);
+        assert_ne!(map.get(id).unwrap().tokens, before);
+        assert_eq!(map.get(id).unwrap().lines_ids.to_vec(), vec![2, 3, 4]);
+
+        assert_eq!(map.parse(inputs[5]).template_id, 3);
+        assert_eq!(map.next_template_id, 4);
+        assert!(map.get(4).is_none());
+    }
+
+    #[test]
+    fn observers_are_notified() {
+        use std::sync::{Arc, Mutex};
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut map = LcsMap::new();
+        map.add_observer(Recorder(events.clone()));
+        var_log_messages_lines()
+            .iter()
+            .for_each(|input| map.insert(input));
+
+        let events = events.lock().unwrap();
+        assert_eq!(
+            *events,
+            vec![
+                "new 0",
+                "new 1",
+                "new 2",
+                "updated 2: Jan 22 04:16:40 combo su(pam_unix)[21719]: session opened for user news by (uid=0) -> Jan 22 * combo su(pam_unix)[21719]: session * for user news",
+                "matched 2 3",
+                "updated 2: Jan 22 * combo su(pam_unix)[21719]: session * for user news -> Jan 22 * combo * user *",
+                "matched 2 4",
+                "new 3",
+            ]
+        );
+    }
+
+    #[test]
+    fn line_id_storages() {
+        let inputs = var_log_messages_lines();
+        let expected = fixtures_output_original_impl();
+
+        let mut map =
+            LcsMap::with_config(SpellConfig::default().with_line_ids(LineIdStorage::Bitmap));
+        inputs.iter().for_each(|input| map.insert(input));
+        assert_eq!(map.to_string(), expected[5]);
+
+        for &storage in &[LineIdStorage::Count, LineIdStorage::Recent(2)] {
+            let mut map = LcsMap::with_config(SpellConfig::default().with_line_ids(storage));
+            inputs.iter().for_each(|input| map.insert(input));
+            assert_eq!(map.seq[2].count(), 3);
+            assert!(map
+                .to_string()
+                .ends_with("6 total entries found, 6 expected."));
+        }
+    }
+
+    #[test]
+    fn parse_log_with_log_format() {
+        let inputs = var_log_messages_lines();
+
+        let log_format =
+            LogFormat::new("<Month> <Day> <Time> <Host> <Component>: <Content>").unwrap();
+        let mut map = LcsMap::with_log_format(log_format);
+        let parsed: Vec<_> = inputs.iter().map(|input| map.parse(input)).collect();
+
+        assert_eq!(
+            parsed[3].headers,
+            vec![
+                ("Month".to_string(), "Jan"),
+                ("Day".to_string(), "22"),
+                ("Time".to_string(), "04:16:41"),
+                ("Host".to_string(), "combo"),
+                ("Component".to_string(), "su(pam_unix)[21719]"),
+            ]
+        );
+        assert_eq!(parsed[3].template_id, parsed[2].template_id);
+        assert_eq!(parsed[3].params, vec![vec!["closed"]]);
+
+        let templates: Vec<_> = map
+            .seq
+            .iter()
+            .map(|obj| obj.template(&map.interner).join(" "))
+            .collect();
+        assert_eq!(
+            templates,
+            vec![
+                "restart.",
+                "ALERT exited abnormally with [1]",
+                "session * for user news",
+                "check pass; user unknown",
+                "authentication failure; logname= uid=0 euid=0 tty=NODEVssh ruser= rhost=server3.sugolan.hu",
+            ]
+        );
+    }
+
+    #[test]
+    fn classify_like_insert() {
+        let log_format =
+            LogFormat::new("<Month> <Day> <Time> <Host> <Component>: <Content>").unwrap();
+        let mut map = LcsMap::with_log_format(log_format);
+        map.masking = vec![MaskingRule::new(r"\d+", "<NUM>").unwrap()];
+        map.insert("Jan 22 04:11:04 combo kernel: Out of memory: Kill process 1234");
+        map.insert("Jan 22 04:11:05 combo sshd[42]: Accepted password for news");
+        let expected = map.to_string();
+
+        let line = "Feb 3 12:00:00 other kernel: Out of memory: Kill process 77";
+        assert_eq!(map.classify(line), Some(0));
+        assert_eq!(
+            map.classify("Feb 3 12:00:00 other kernel: Server started"),
+            None
+        );
+        assert_eq!(map.to_string(), expected);
+        assert_eq!(map.line_id, 2);
+        assert_eq!(map.insert_returning_id(line), 0);
+    }
+
+    #[test]
+    fn parse_log_with_masking() {
+        let masking = vec![
+            MaskingRule::new(r"\d+:\d+:\d+", "<TIME>").unwrap(),
+            MaskingRule::new(r"\[\d+\]", "[<PID>]").unwrap(),
+        ];
+        let mut map = LcsMap::with_masking(masking);
+        let parsed: Vec<_> = var_log_messages_lines()
+            .iter()
+            .map(|input| map.parse(input))
+            .collect();
+
+        let templates: Vec<_> = map
+            .seq
+            .iter()
+            .map(|obj| obj.template(&map.interner).join(" "))
+            .collect();
+        assert_eq!(templates[0], "Jan 22 <TIME> combo syslogd 1.4.1: restart.");
+        assert_eq!(templates[2], "Jan 22 <TIME> combo * user *");
+
+        assert_eq!(parsed[0].params, vec![vec!["04:11:04"]]);
+        assert_eq!(
+            parsed[3].params,
+            vec![
+                vec!["04:16:41"],
+                vec!["su(pam_unix)[21719]:"],
+                vec!["closed"]
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_literal_replacement() {
+        let masking = vec![MaskingRule::new(r"\d+\.\d+\.\d+\.\d+", "<IP>").unwrap()];
+        let mut map = LcsMap::with_masking(masking);
+        map.parse("Route <IP> via 10.0.0.1");
+        let parsed = map.parse("Route <IP> via 10.0.0.2");
+
+        assert_eq!(
+            map.seq[0].template(&map.interner),
+            vec!["Route", "<IP>", "via", "<IP>"]
+        );
+        assert_eq!(parsed.params, vec![vec!["10.0.0.2"]]);
+    }
+
+    #[test]
+    fn parse_log_with_config() {
+        let inputs = var_log_messages_lines();
+
+        let mut map = LcsMap::with_config(SpellConfig::default().with_tau(0.9));
+        inputs.iter().for_each(|input| map.insert(input));
+        assert_eq!(map.seq.len(), 6);
+
+        let mut map = LcsMap::with_config(SpellConfig::default().without_pruning());
+        inputs.iter().for_each(|input| map.insert(input));
+        assert_eq!(map.seq.len(), 4);
+        assert_eq!(map.seq[2].lines_ids.to_vec(), vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn equality_compares_wildcard_kinds() {
+        let mut numbers = LcsMap::new();
+        numbers.insert("Connection closed by 42");
+        numbers.insert("Connection closed by 43");
+        let mut names = LcsMap::new();
+        names.insert("Connection closed by 42");
+        names.insert("Connection closed by peer");
+        assert_eq!(numbers.to_string(), names.to_string());
+        assert_ne!(numbers, names);
+
+        names.seq[0].tokens[3] = TemplateToken::Wildcard(ParamType::Int.into());
+        assert_eq!(numbers, names);
+    }
+
+    #[test]
+    fn equality_compares_tracked_values() {
+        let config = SpellConfig::default().with_tracked_values(4);
+        let mut map = LcsMap::with_config(config.clone());
+        map.insert("Connection closed by 42");
+        map.insert("Connection closed by 43");
+        let mut other = LcsMap::with_config(config);
+        other.insert("Connection closed by 43");
+        other.insert("Connection closed by 42");
+        assert_ne!(map, other);
+
+        map.seq[0].params = other.seq[0].params.clone();
+        assert_eq!(map, other);
+    }
+
     #[test]
     fn parse_log_with_delimiters() {
-        let inputs = fixtures_input_var_log_messages_lines();
+        let inputs = var_log_messages_lines();
 
         let mut map = LcsMap::with_delimiters(vec![' ', ':']);
-        let expected = LcsMap {
-            seq: Vec::new(),
-            line_id: 0,
-            delimiters: vec![' ', ':'],
-        };
+        let expected = LcsMap::with_delimiters(vec![' ', ':']);
         assert_eq!(map, expected);
 
         map.insert(inputs[0]);
         let expected = LcsMap {
-            seq: vec![LcsObject {
-                tokens: [
-                    "Jan", "22", "04", "11", "04", "combo", "syslogd", "1.4.1", "restart.",
-                ]
-                .iter()
-                .map(|s| s.to_string())
-                .collect(),
-                lines_ids: vec![0],
-            }],
-            line_id: 1,
-            delimiters: vec![' ', ':'],
+            tokenizer: vec![' ', ':'],
+            ..expected_map(
+                vec![object(
+                    0,
+                    &[
+                        "Jan", "22", "04", "11", "04", "combo", "syslogd", "1.4.1", "restart.",
+                    ],
+                    vec![0],
+                )],
+                1,
+                1,
+            )
         };
         assert_eq!(map, expected);
     }
+
+    #[test]
+    fn parse_log_with_tokenizer() {
+        use crate::tokenizer::KeepDelimiters;
+
+        let mut map = LcsMap::with_tokenizer(KeepDelimiters::new(vec![' '], &["="]));
+        map.insert("session opened for logname= uid=0 euid=0");
+        map.insert("session opened for logname= uid=500 euid=500");
+        assert_eq!(map.seq.len(), 1);
+        assert_eq!(
+            map.seq[0].template(&map.interner),
+            vec!["session", "opened", "for", "logname", "=", "uid", "=", "*", "euid", "=", "*"]
+        );
+
+        let parsed = map.parse("session opened for logname= uid=42 euid=0");
+        assert_eq!(parsed.template_id, 0);
+        assert_eq!(parsed.params, vec![vec!["42"], vec!["0"]]);
+    }
+
+    #[test]
+    fn merge_with_offset() {
+        let mut map = LcsMap::new();
+        map.insert("Temperature (41C) exceeds warning threshold");
+        map.insert("Command Failed on: node-127");
+
+        let mut other = LcsMap::new();
+        other.insert("Server started");
+        other.insert("Command Failed on: node-234");
+        other.insert("Temperature (42C, 43C) exceeds warning threshold");
+
+        assert_eq!(
+            map.merge(other, LineOffset::Offset(100)).unwrap(),
+            vec![2, 1, 0]
+        );
+        assert_eq!(map.line_id, 103);
+        assert_eq!(map.next_template_id, 3);
+        let templates: Vec<_> = map
+            .seq
+            .iter()
+            .map(|obj| {
+                (
+                    obj.template(&map.interner).join(" "),
+                    obj.lines_ids.to_vec(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            templates,
+            vec![
+                (
+                    "Temperature * exceeds warning threshold".to_string(),
+                    vec![0, 102]
+                ),
+                ("Command Failed on: *".to_string(), vec![1, 101]),
+                ("Server started".to_string(), vec![100]),
+            ]
+        );
+        assert_eq!(map.seq[1].param_types(), vec![Some(ParamType::Text)]);
+    }
+
+    #[test]
+    fn merge_hosts() {
+        let inputs = var_log_messages_lines();
+        let mut host_a =
+            LcsMap::with_config(SpellConfig::default().with_line_ids(LineIdStorage::Bitmap));
+        inputs[..3].iter().for_each(|input| host_a.insert(input));
+        let mut host_b = LcsMap::new();
+        inputs[3..].iter().for_each(|input| host_b.insert(input));
+
+        let mut map =
+            LcsMap::with_config(SpellConfig::default().with_line_ids(LineIdStorage::Bitmap));
+        assert_eq!(
+            map.merge(host_a, LineOffset::Source(0)).unwrap(),
+            vec![0, 1, 2]
+        );
+        assert_eq!(
+            map.merge(host_b, LineOffset::Source(1)).unwrap(),
+            vec![2, 3]
+        );
+        let billion = LineOffset::LINES_PER_SOURCE;
+        assert_eq!(map.line_id, billion + 3);
+        assert_eq!(
+            map.seq[2].template(&map.interner),
+            vec!["Jan", "22", "*", "combo", "*", "user", "*"]
+        );
+        assert_eq!(
+            map.seq
+                .iter()
+                .map(|obj| obj.lines_ids.to_vec())
+                .collect::<Vec<_>>(),
+            vec![
+                vec![0],
+                vec![1],
+                vec![2, billion, billion + 1],
+                vec![billion + 2]
+            ]
+        );
+        let sources: Vec<_> = map.seq[2]
+            .lines_ids
+            .iter()
+            .map(LineOffset::source_of)
+            .collect();
+        assert_eq!(sources, vec![0, 1, 1]);
+    }
+
+    #[test]
+    fn merge_overlapping_lines() {
+        let inputs = var_log_messages_lines();
+        let mut map =
+            LcsMap::with_config(SpellConfig::default().with_line_ids(LineIdStorage::Bitmap));
+        inputs[..3].iter().for_each(|input| map.insert(input));
+        let mut other = LcsMap::new();
+        inputs[3..].iter().for_each(|input| other.insert(input));
+
+        // Ids 2 to 4 would be counted once by the bitmap, so they are rejected
+        let expected = map.to_string();
+        assert!(matches!(
+            map.merge(other, LineOffset::Offset(2)),
+            Err(Error::OverlappingLines(2, 3))
+        ));
+        assert_eq!(map.to_string(), expected);
+        assert_eq!(map.line_id, 3);
+    }
 }
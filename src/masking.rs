@@ -0,0 +1,253 @@
+use std::{borrow::Cow, convert::TryFrom, ops::Range};
+
+use regex::Regex;
+use serde_derive::*;
+
+use crate::error::{Error, Result};
+
+/// Replaces the parts of a line matching a regular expression by a fixed
+/// token before tokenization, so that obvious variables never reach the LCS.
+///
+/// # Examples
+///
+/// ```
+/// use spell::masking::{mask, MaskingRule};
+///
+/// let rules = vec![
+///     MaskingRule::new(r"\d+\.\d+\.\d+\.\d+", "<IP>").unwrap(),
+///     MaskingRule::new(r"\b\d+\b", "<NUM>").unwrap(),
+/// ];
+/// let masked = mask(&rules, "Sent 42 bytes to 10.0.0.1");
+///
+/// assert_eq!(masked.text(), "Sent <NUM> bytes to <IP>");
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "MaskingRuleSpec", into = "MaskingRuleSpec")]
+pub struct MaskingRule {
+    regex: Regex,
+    replacement: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MaskingRuleSpec {
+    pattern: String,
+    replacement: String,
+}
+
+impl MaskingRule {
+    pub fn new(pattern: &str, replacement: &str) -> Result<MaskingRule> {
+        Ok(MaskingRule {
+            regex: Regex::new(pattern)?,
+            replacement: replacement.to_string(),
+        })
+    }
+
+    pub fn pattern(&self) -> &str {
+        self.regex.as_str()
+    }
+
+    pub fn replacement(&self) -> &str {
+        &self.replacement
+    }
+}
+
+impl PartialEq for MaskingRule {
+    fn eq(&self, other: &MaskingRule) -> bool {
+        self.pattern() == other.pattern() && self.replacement == other.replacement
+    }
+}
+
+impl TryFrom<MaskingRuleSpec> for MaskingRule {
+    type Error = Error;
+
+    fn try_from(spec: MaskingRuleSpec) -> Result<MaskingRule> {
+        MaskingRule::new(&spec.pattern, &spec.replacement)
+    }
+}
+
+impl From<MaskingRule> for MaskingRuleSpec {
+    fn from(rule: MaskingRule) -> MaskingRuleSpec {
+        MaskingRuleSpec {
+            pattern: rule.regex.as_str().to_string(),
+            replacement: rule.replacement,
+        }
+    }
+}
+
+/// A line with its variables masked, remembering the masked values.
+#[derive(Debug, Clone)]
+pub struct Masked<'a> {
+    original: &'a str,
+    text: Cow<'a, str>,
+    /// Byte ranges of each replacement in `text` and of the value it replaced
+    /// in `original`, in increasing order.
+    spans: Vec<(Range<usize>, Range<usize>)>,
+}
+
+/// Apply the masking `rules` to `line`.
+///
+/// Rules are tried in order: a match overlapping a value already masked by a
+/// previous rule is ignored.
+pub fn mask<'a>(rules: &[MaskingRule], line: &'a str) -> Masked<'a> {
+    let mut matches: Vec<(Range<usize>, &str)> = Vec::new();
+    for rule in rules {
+        for m in rule.regex.find_iter(line) {
+            if m.start() == m.end() {
+                continue;
+            }
+            let overlaps = matches
+                .iter()
+                .any(|(range, _)| m.start() < range.end && range.start < m.end());
+            if !overlaps {
+                matches.push((m.range(), &rule.replacement));
+            }
+        }
+    }
+
+    if matches.is_empty() {
+        return Masked {
+            original: line,
+            text: Cow::Borrowed(line),
+            spans: Vec::new(),
+        };
+    }
+    matches.sort_by_key(|(range, _)| range.start);
+
+    let mut text = String::with_capacity(line.len());
+    let mut spans = Vec::with_capacity(matches.len());
+    let mut last = 0;
+    for (range, replacement) in matches {
+        text.push_str(&line[last..range.start]);
+        let start = text.len();
+        text.push_str(replacement);
+        spans.push((start..text.len(), range.clone()));
+        last = range.end;
+    }
+    text.push_str(&line[last..]);
+
+    Masked {
+        original: line,
+        text: Cow::Owned(text),
+        spans,
+    }
+}
+
+impl<'a> Masked<'a> {
+    /// The line with its variables replaced.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Part of the original line corresponding to `slice`, which must be a
+    /// slice of `text()`, such as a token. Replacements are mapped back to the
+    /// values they masked.
+    pub fn original(&self, slice: &str) -> &'a str {
+        let offset = self.offset_of(slice);
+        let start = self.map_offset(offset, false);
+        let end = self.map_offset(offset + slice.len(), true);
+        &self.original[start..end.max(start)]
+    }
+
+    /// Whether `slice`, which must be a slice of `text()`, overlaps a
+    /// replacement. Text of the line that merely looks like a replacement is
+    /// not masked.
+    pub fn is_masked(&self, slice: &str) -> bool {
+        let start = self.offset_of(slice);
+        let end = start + slice.len();
+        self.spans
+            .iter()
+            .any(|(masked, _)| start < masked.end && masked.start < end)
+    }
+
+    /// Offset of `slice` in `text()`.
+    fn offset_of(&self, slice: &str) -> usize {
+        let offset = (slice.as_ptr() as usize).wrapping_sub(self.text.as_ptr() as usize);
+        assert!(
+            offset + slice.len() <= self.text.len(),
+            "slice is not part of the masked text"
+        );
+        offset
+    }
+
+    /// Offset in the original line of an `offset` in the masked text. Offsets
+    /// inside a replacement map to the start, or the `end`, of the value.
+    fn map_offset(&self, offset: usize, end: bool) -> usize {
+        let mut shift = 0isize;
+        for (masked, original) in &self.spans {
+            if offset <= masked.start {
+                break;
+            } else if offset < masked.end {
+                return if end { original.end } else { original.start };
+            }
+            shift = original.end as isize - masked.end as isize;
+        }
+        (offset as isize + shift) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenize;
+
+    fn rules() -> Vec<MaskingRule> {
+        vec![
+            MaskingRule::new(r"\d+\.\d+\.\d+\.\d+", "<IP>").unwrap(),
+            MaskingRule::new(r"\b\d+\b", "<NUM>").unwrap(),
+            MaskingRule::new(r"0x[0-9a-fA-F]+", "<HEX>").unwrap(),
+        ]
+    }
+
+    #[test]
+    fn rules_are_ordered() {
+        let masked = mask(
+            &rules(),
+            "Read 512 bytes at 0xff00 from 192.168.0.1 port 22",
+        );
+        assert_eq!(
+            masked.text(),
+            "Read <NUM> bytes at <HEX> from <IP> port <NUM>"
+        );
+    }
+
+    #[test]
+    fn values_are_recovered() {
+        let line = "Read 512 bytes at 0xff00 from 192.168.0.1 port 22";
+        let masked = mask(&rules(), line);
+
+        let originals: Vec<_> = tokenize(masked.text(), &[' '])
+            .map(|token| masked.original(token))
+            .collect();
+        let expected: Vec<_> = tokenize(line, &[' ']).collect();
+        assert_eq!(originals, expected);
+
+        // Slices spanning several tokens
+        let text = masked.text();
+        let start = text.find("<HEX>").unwrap();
+        let end = text.find("port").unwrap();
+        assert_eq!(
+            masked.original(&text[start..end]),
+            "0xff00 from 192.168.0.1 "
+        );
+    }
+
+    #[test]
+    fn literal_replacements_are_not_masked() {
+        let masked = mask(&rules(), "Sent <IP> to 10.0.0.1");
+        assert_eq!(masked.text(), "Sent <IP> to <IP>");
+
+        let tokens: Vec<_> = tokenize(masked.text(), &[' ']).collect();
+        let is_masked: Vec<_> = tokens.iter().map(|t| masked.is_masked(t)).collect();
+        assert_eq!(is_masked, [false, false, false, true]);
+        assert_eq!(masked.original(tokens[1]), "<IP>");
+        assert_eq!(masked.original(tokens[3]), "10.0.0.1");
+    }
+
+    #[test]
+    fn nothing_masked() {
+        let line = "Nothing to see here";
+        let masked = mask(&rules(), line);
+        assert_eq!(masked.text(), line);
+        assert_eq!(masked.original(&masked.text()[11..14]), "see");
+    }
+}
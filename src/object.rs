@@ -1,80 +1,332 @@
+use std::ops::Range;
+
 use serde_derive::*;
 
-use crate::{tokenize, LcsSeq, LineId, LcsDelimiters};
+use crate::{
+    config::SpellConfig,
+    interner::Interner,
+    lcs::{self, LcsAlgorithm},
+    line_ids::{LineIdStorage, LineIds},
+    refine::ParamValues,
+    template::{self, ParamKinds, ParamType, TemplateToken},
+    tokenize, LcsDelimiters, LcsSeq, LcsTemplate, LcsToken, LineId, TemplateId,
+};
 
 #[derive(Default, Debug, PartialEq, Serialize, Deserialize)]
 pub struct LcsObject {
-    pub tokens: LcsSeq,
-    pub lines_ids: Vec<LineId>,
+    /// Identifier assigned by the `LcsMap` when the template is created, kept
+    /// when the template gets generalized.
+    pub id: TemplateId,
+    /// Tokens of the template, constants being interned by the map's `Interner`.
+    pub tokens: LcsTemplate,
+    pub lines_ids: LineIds,
+    /// Values absorbed by the wildcards, when tracked.
+    #[serde(default)]
+    pub params: Option<ParamValues>,
 }
 
 impl LcsObject {
-    pub fn from_str(s: &str, line_id: LineId, delimiters: LcsDelimiters) -> LcsObject {
-        let tokens = tokenize(s, delimiters.as_slice()).map(|s| s.to_string()).collect();
+    pub fn from_str(
+        s: &str,
+        line_id: LineId,
+        delimiters: LcsDelimiters,
+        interner: &mut Interner,
+    ) -> LcsObject {
+        let tokens = tokenize(s, delimiters.as_slice())
+            .map(|s| interner.intern(s))
+            .collect();
         LcsObject::new(tokens, line_id)
     }
 
-    pub fn from_str_slice(tokens: &[&str], line_id: LineId) -> LcsObject {
-        LcsObject {
-            tokens: tokens.iter().map(|s| s.to_string()).collect(),
-            lines_ids: vec![line_id],
-        }
+    pub fn from_str_slice(tokens: &[&str], line_id: LineId, interner: &mut Interner) -> LcsObject {
+        LcsObject::new(tokens.iter().map(|s| interner.intern(s)).collect(), line_id)
     }
 
+    /// Constructor for a template made of the tokens of a line.
     pub fn new(tokens: LcsSeq, line_id: LineId) -> LcsObject {
+        LcsObject::with_storage(tokens, line_id, LineIdStorage::default())
+    }
+
+    /// Constructor choosing how the identifiers of the lines are kept.
+    pub fn with_storage(tokens: LcsSeq, line_id: LineId, storage: LineIdStorage) -> LcsObject {
+        let mut lines_ids = LineIds::new(storage);
+        lines_ids.push(line_id);
         LcsObject {
-            tokens,
-            lines_ids: vec![line_id],
+            id: 0,
+            tokens: tokens.into_iter().map(TemplateToken::Constant).collect(),
+            lines_ids,
+            params: None,
         }
     }
 
+    /// Constructor storing the lines, and tracking the values of the
+    /// wildcards, as set by `config`.
+    pub(crate) fn with_config(tokens: LcsSeq, line_id: LineId, config: &SpellConfig) -> LcsObject {
+        let mut obj = LcsObject::with_storage(tokens, line_id, config.line_ids);
+        if let Some(limit) = config.tracked_values {
+            if ParamValues::is_supported(config.line_ids) {
+                obj.params = Some(ParamValues::new(limit));
+            }
+        }
+        obj
+    }
+
     pub fn get_lcs(&self, other: &LcsSeq) -> usize {
-        let mut count = 0;
+        self.get_lcs_with(other, LcsAlgorithm::Greedy)
+    }
 
-        // Simple loop implementation from the paper
-        let mut last_match = 0;
-        for s_left in self.tokens.iter().filter(|s| *s != "*") {
-            for (j, s_right) in other.iter().enumerate().skip(last_match) {
-                if s_left == s_right {
-                    last_match = j + 1;
-                    count += 1;
-                    break;
-                }
+    pub fn get_lcs_with(&self, other: &LcsSeq, algorithm: LcsAlgorithm) -> usize {
+        lcs::length(&self.tokens, other, algorithm)
+    }
+
+    /// Insert a new line in the object, generalizing the template if required.
+    ///
+    /// Returns the previous tokens if the template changed. The kinds of the
+    /// values absorbed by the wildcards are updated in any case.
+    pub fn insert(
+        &mut self,
+        tokens: &[&str],
+        line_id: LineId,
+        interner: &Interner,
+    ) -> Option<LcsTemplate> {
+        self.insert_with(tokens, line_id, interner, LcsAlgorithm::Greedy)
+    }
+
+    /// Same as `insert()`, merging the template with the line using the LCS
+    /// found by `algorithm`.
+    pub fn insert_with(
+        &mut self,
+        tokens: &[&str],
+        line_id: LineId,
+        interner: &Interner,
+        algorithm: LcsAlgorithm,
+    ) -> Option<LcsTemplate> {
+        let symbols = interner.lookup(tokens);
+        self.insert_symbols(&symbols, tokens, line_id, interner, algorithm)
+    }
+
+    /// Same as `insert_with()`, for a line already looked up in `interner`.
+    pub(crate) fn insert_symbols(
+        &mut self,
+        symbols: &[LcsToken],
+        tokens: &[&str],
+        line_id: LineId,
+        interner: &Interner,
+        algorithm: LcsAlgorithm,
+    ) -> Option<LcsTemplate> {
+        self.lines_ids.push(line_id);
+        let previous = self.generalize(symbols, interner, algorithm, |i| {
+            ParamType::of(tokens[i]).into()
+        });
+        if let Some(params) = &mut self.params {
+            params.insert(
+                &self.tokens,
+                previous.as_deref(),
+                self.lines_ids.iter().filter(|&id| id != line_id),
+                symbols,
+                tokens,
+                line_id,
+                interner,
+            );
+        }
+        previous
+    }
+
+    /// Whether `insert_symbols()` would change the tokens of the template,
+    /// either generalizing it or widening the kinds of its wildcards.
+    pub(crate) fn is_changed_by(
+        &self,
+        symbols: &[LcsToken],
+        tokens: &[&str],
+        algorithm: LcsAlgorithm,
+    ) -> bool {
+        let merged = self.merge_with(symbols, algorithm);
+        if !template::same_shape(&merged.tokens, &self.tokens) {
+            return true;
+        }
+        let wildcards = self.tokens.iter().filter_map(|s| match *s {
+            TemplateToken::Wildcard(kinds) => Some(kinds),
+            TemplateToken::Constant(_) => None,
+        });
+        wildcards.zip(merged.spans).any(|(kinds, (_, other))| {
+            let mut extended = kinds;
+            extended.extend(span_kinds(other, |i| ParamType::of(tokens[i]).into()));
+            extended != kinds
+        })
+    }
+
+    /// Generalize the template to also cover `other`, a template of another
+    /// map whose tokens are resolved by `other_interner`. Its lines are added
+    /// with their identifiers shifted by `line_offset`.
+    ///
+    /// `symbols` are the tokens of `other` looked up in `interner`, wildcards
+    /// being `Symbol::UNKNOWN`. The values of the wildcards stop being tracked.
+    pub(crate) fn absorb(
+        &mut self,
+        other: &LcsObject,
+        symbols: &[LcsToken],
+        other_interner: &Interner,
+        interner: &Interner,
+        line_offset: LineId,
+        algorithm: LcsAlgorithm,
+    ) -> Option<LcsTemplate> {
+        self.lines_ids.extend_from(&other.lines_ids, line_offset);
+        self.params = None;
+        self.generalize(symbols, interner, algorithm, |i| {
+            other.tokens[i].kinds(other_interner)
+        })
+    }
+
+    /// Merge the template with `symbols`, returning the previous tokens if
+    /// the template changed.
+    ///
+    /// Wildcards absorb the kinds of the values of the previous template, as
+    /// well as the ones of `symbols` given by `kinds_at`.
+    fn generalize<F>(
+        &mut self,
+        symbols: &[LcsToken],
+        interner: &Interner,
+        algorithm: LcsAlgorithm,
+        kinds_at: F,
+    ) -> Option<LcsTemplate>
+    where
+        F: Fn(usize) -> ParamKinds,
+    {
+        let Merged { mut tokens, spans } = self.merge_with(symbols, algorithm);
+
+        if template::same_shape(&tokens, &self.tokens) {
+            // Each wildcard stands for itself in the previous template
+            for (kinds, (_, other)) in wildcard_kinds(&mut self.tokens).zip(spans) {
+                kinds.extend(span_kinds(other, &kinds_at));
             }
+            return None;
         }
 
-        count
+        for (kinds, (previous, other)) in wildcard_kinds(&mut tokens).zip(spans) {
+            kinds.extend(span_kinds(previous, |i| self.tokens[i].kinds(interner)));
+            kinds.extend(span_kinds(other, &kinds_at));
+        }
+        Some(std::mem::replace(&mut self.tokens, tokens))
     }
 
-    pub fn insert(&mut self, tokens: LcsSeq, line_id: LineId) {
-        self.lines_ids.push(line_id);
-        let mut tmp = String::with_capacity(256);
+    /// Merge the template with `symbols` using the LCS found by `algorithm`.
+    fn merge_with(&self, symbols: &[LcsToken], algorithm: LcsAlgorithm) -> Merged {
+        match algorithm {
+            LcsAlgorithm::Greedy => self.merge_greedy(symbols),
+            _ => self.merge(symbols, algorithm),
+        }
+    }
+
+    /// Merge as done by the original implementation.
+    ///
+    /// Template tokens found after the last token of the line are dropped.
+    fn merge_greedy(&self, tokens: &[LcsToken]) -> Merged {
+        let mut merged = Merged::with_capacity(self.tokens.len());
         let mut last_match = 0;
-        let mut placeholder = false;
-        for s_left in self.tokens.iter() {
-            if s_left == "*" {
-                if !placeholder {
-                    tmp.push_str("* ");
+        for (i, &s_left) in self.tokens.iter().enumerate() {
+            let constant = match s_left {
+                TemplateToken::Constant(constant) => constant,
+                TemplateToken::Wildcard(_) => {
+                    merged.push_wildcard(i..i + 1, last_match..last_match);
+                    continue;
                 }
-                placeholder = true;
-                continue;
-            }
+            };
 
-            for (j, s_right) in tokens.iter().enumerate().skip(last_match) {
-                if s_left == s_right {
-                    placeholder = false;
-                    tmp.push_str(&s_left);
-                    tmp.push(' ');
-                    last_match = j + 1;
-                    break;
-                } else if !placeholder {
-                    tmp.push_str("* ");
-                    placeholder = true;
+            match tokens[last_match..].iter().position(|&s| s == constant) {
+                Some(skipped) => {
+                    if skipped > 0 {
+                        merged.push_wildcard(i..i, last_match..last_match + skipped);
+                    }
+                    merged.tokens.push(s_left);
+                    last_match += skipped + 1;
+                }
+                None if last_match < tokens.len() => {
+                    merged.push_wildcard(i..i + 1, last_match..last_match);
                 }
+                None => {}
             }
         }
+        merged.extend_last_wildcard(self.tokens.len(), tokens.len());
+
+        merged
+    }
+
+    /// Keep the tokens of the LCS, replacing everything in between by a
+    /// single placeholder.
+    fn merge(&self, tokens: &[LcsToken], algorithm: LcsAlgorithm) -> Merged {
+        let mut merged = Merged::with_capacity(self.tokens.len());
+        let (mut next_left, mut next_right) = (0, 0);
+        for (i, j) in lcs::alignment(&self.tokens, tokens, algorithm) {
+            if i > next_left || j > next_right {
+                merged.push_wildcard(next_left..i, next_right..j);
+            }
+            merged.tokens.push(self.tokens[i]);
+            next_left = i + 1;
+            next_right = j + 1;
+        }
+        if next_left < self.tokens.len() || next_right < tokens.len() {
+            merged.push_wildcard(next_left..self.tokens.len(), next_right..tokens.len());
+        }
+
+        merged
+    }
+
+    /// Tokens of the line filling each placeholder of the template, in order.
+    ///
+    /// When the line doesn't fully match the template, the constant tokens are
+    /// aligned using the LCS and tokens not covered by a placeholder are ignored.
+    pub fn extract_parameters<'a>(
+        &self,
+        tokens: &[&'a str],
+        interner: &Interner,
+    ) -> Vec<Vec<&'a str>> {
+        self.extract_parameters_with(tokens, interner, |_| false)
+    }
+
+    /// Same as `extract_parameters()`, but constant tokens are also parameters
+    /// when `is_variable` returns `true` for the token of the line they
+    /// matched (for example a masked value).
+    pub fn extract_parameters_with<'a, F>(
+        &self,
+        tokens: &[&'a str],
+        interner: &Interner,
+        is_variable: F,
+    ) -> Vec<Vec<&'a str>>
+    where
+        F: Fn(&str) -> bool,
+    {
+        let symbols = interner.lookup(tokens);
+        let is_variable = |i: usize, j: usize| match self.tokens[i] {
+            TemplateToken::Constant(_) => is_variable(tokens[j]),
+            TemplateToken::Wildcard(_) => false,
+        };
+        parameter_spans(&self.tokens, &symbols, is_variable)
+            .into_iter()
+            .map(|span| tokens[span].to_vec())
+            .collect()
+    }
+
+    /// Types of the values absorbed by each wildcard of the template, in order.
+    pub fn param_types(&self) -> Vec<Option<ParamType>> {
+        self.tokens
+            .iter()
+            .filter(|s| s.is_wildcard())
+            .map(|s| s.param_type())
+            .collect()
+    }
 
-        self.tokens = tmp.trim().split(' ').map(|s| s.to_string()).collect();
+    /// Tokens of the template that are not placeholders.
+    pub fn constants(&self) -> impl Iterator<Item = &LcsToken> {
+        template::constants(&self.tokens)
+    }
+
+    /// Whether all constant tokens of the template appear, in order, in `other`.
+    ///
+    /// This is the "simple loop" lookup from the paper.
+    pub fn is_subsequence_of(&self, other: &[LcsToken]) -> bool {
+        let mut other = other.iter();
+        self.constants()
+            .all(|s_left| other.any(|s_right| s_left == s_right))
     }
 
     pub fn length(&self) -> usize {
@@ -82,25 +334,191 @@ impl LcsObject {
     }
 
     pub fn count(&self) -> usize {
-        self.lines_ids.len()
+        self.lines_ids.count()
+    }
+
+    /// Strings of the tokens of the template, `*` standing for wildcards.
+    pub fn template<'a>(&self, interner: &'a Interner) -> Vec<&'a str> {
+        self.tokens.iter().map(|s| s.resolve(interner)).collect()
+    }
+
+    /// Same as `template()`, but wildcards are shown with the type of the
+    /// values they absorbed, like `<FLOAT>`.
+    pub fn typed_template<'a>(&self, interner: &'a Interner) -> Vec<&'a str> {
+        self.tokens
+            .iter()
+            .map(|s| s.resolve_typed(interner))
+            .collect()
+    }
+
+    /// Printable template, resolving the tokens with the map's interner.
+    pub fn display<'a>(&'a self, interner: &'a Interner) -> LcsObjectDisplay<'a> {
+        LcsObjectDisplay {
+            object: self,
+            interner,
+        }
+    }
+}
+
+/// Kinds of the values in a parameter span, a span of several tokens being text.
+fn span_kinds<F>(span: Range<usize>, kinds_at: F) -> ParamKinds
+where
+    F: Fn(usize) -> ParamKinds,
+{
+    match span.len() {
+        0 => ParamKinds::default(),
+        1 => kinds_at(span.start),
+        _ => ParamType::Text.into(),
     }
 }
 
-impl std::fmt::Display for LcsObject {
+/// Template merged with a line, remembering what each wildcard stands for.
+struct Merged {
+    tokens: LcsTemplate,
+    /// Ranges of the tokens of the previous template and of the line replaced
+    /// by each wildcard, in order.
+    spans: Vec<(Range<usize>, Range<usize>)>,
+}
+
+impl Merged {
+    fn with_capacity(capacity: usize) -> Merged {
+        Merged {
+            tokens: Vec::with_capacity(capacity),
+            spans: Vec::new(),
+        }
+    }
+
+    /// Add a wildcard, or extend the last token if it already is one, so
+    /// that wildcards are never adjacent.
+    fn push_wildcard(&mut self, previous: Range<usize>, other: Range<usize>) {
+        if self.tokens.last().map(|s| s.is_wildcard()).unwrap_or(false) {
+            self.extend_last_wildcard(previous.end, other.end);
+        } else {
+            self.tokens.push(TemplateToken::wildcard());
+            self.spans.push((previous, other));
+        }
+    }
+
+    /// Make the last wildcard, if the template ends with one, stand for the
+    /// tokens up to `previous_end` and `other_end`.
+    fn extend_last_wildcard(&mut self, previous_end: usize, other_end: usize) {
+        if self.tokens.last().map(|s| s.is_wildcard()).unwrap_or(false) {
+            let (previous, other) = self.spans.last_mut().unwrap();
+            previous.end = previous_end;
+            other.end = other_end;
+        }
+    }
+}
+
+/// Kinds of each wildcard of `template`, in order.
+fn wildcard_kinds(template: &mut [TemplateToken]) -> impl Iterator<Item = &mut ParamKinds> {
+    template.iter_mut().filter_map(|s| match s {
+        TemplateToken::Wildcard(kinds) => Some(kinds),
+        TemplateToken::Constant(_) => None,
+    })
+}
+
+/// Ranges of `tokens` filling each placeholder of `template`, in order, as
+/// well as the single token matched by each constant `template[i]` for which
+/// `is_variable(i, j)` is `true`, `j` being the index of the token.
+pub(crate) fn parameter_spans<F>(
+    template: &[TemplateToken],
+    tokens: &[LcsToken],
+    is_variable: F,
+) -> Vec<Range<usize>>
+where
+    F: Fn(usize, usize) -> bool,
+{
+    let (n, m) = (template.len(), tokens.len());
+    let width = m + 1;
+    let is_placeholder = |i: usize| template[i].is_wildcard();
+
+    // matches[i * width + j]: does tokens[j..] match template[i..], where a
+    // placeholder stands for any number of tokens?
+    let mut matches = vec![false; (n + 1) * width];
+    matches[n * width + m] = true;
+    for i in (0..n).rev() {
+        for j in (0..=m).rev() {
+            matches[i * width + j] = if is_placeholder(i) {
+                matches[(i + 1) * width + j] || (j < m && matches[i * width + j + 1])
+            } else {
+                j < m
+                    && template[i] == TemplateToken::Constant(tokens[j])
+                    && matches[(i + 1) * width + j + 1]
+            };
+        }
+    }
+
+    let mut spans = Vec::new();
+    if matches[0] {
+        let mut j = 0;
+        for i in 0..n {
+            if is_placeholder(i) {
+                // Placeholders match as few tokens as possible
+                let end = (j..=m).find(|&k| matches[(i + 1) * width + k]).unwrap_or(m);
+                spans.push(j..end);
+                j = end;
+            } else {
+                if is_variable(i, j) {
+                    spans.push(j..j + 1);
+                }
+                j += 1;
+            }
+        }
+    } else {
+        let mut matched = vec![None; n];
+        for (i, j) in lcs::alignment(template, tokens, LcsAlgorithm::DynamicProgramming) {
+            matched[i] = Some(j);
+        }
+        let mut start = 0;
+        let mut consumed = false;
+        for i in 0..n {
+            if let Some(j) = matched[i] {
+                if is_variable(i, j) {
+                    spans.push(j..j + 1);
+                }
+                start = j + 1;
+                consumed = false;
+            } else if is_placeholder(i) {
+                if consumed {
+                    spans.push(0..0);
+                } else {
+                    let end = matched[i + 1..]
+                        .iter()
+                        .flatten()
+                        .next()
+                        .cloned()
+                        .unwrap_or(m);
+                    spans.push(start..end);
+                    consumed = true;
+                }
+            }
+        }
+    }
+
+    spans
+}
+
+/// Helper to print an `LcsObject` with the strings of its tokens, returned by
+/// `LcsObject::display()`. The alternate format (`{:#}`) shows the types of
+/// the wildcards.
+pub struct LcsObjectDisplay<'a> {
+    object: &'a LcsObject,
+    interner: &'a Interner,
+}
+
+impl<'a> std::fmt::Display for LcsObjectDisplay<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let template = if f.alternate() {
+            self.object.typed_template(self.interner)
+        } else {
+            self.object.template(self.interner)
+        };
         write!(
             f,
             "{}\n\t\t{{{}}}",
-            self.tokens
-                .iter()
-                .map(|s| &**s)
-                .collect::<Vec<&str>>()
-                .join(" "),
-            self.lines_ids
-                .iter()
-                .map(|i| i.to_string())
-                .collect::<Vec<String>>()
-                .join(", ")
+            template.join(" "),
+            self.object.lines_ids
         )
     }
 }
@@ -109,9 +527,201 @@ impl std::fmt::Display for LcsObject {
 mod tests {
     use super::*;
 
+    /// Object whose template is `tokens`, where `*` are wildcards
+    fn object(interner: &mut Interner, tokens: &[&str]) -> LcsObject {
+        let tokens = tokens
+            .iter()
+            .map(|s| match *s {
+                "*" => TemplateToken::wildcard(),
+                _ => TemplateToken::Constant(interner.intern(s)),
+            })
+            .collect();
+        LcsObject {
+            tokens,
+            ..LcsObject::new(Vec::new(), 0)
+        }
+    }
+
     #[test]
     fn constructor() {
         let input = "Command Failed on: node-127";
-        let _lcs_object = LcsObject::from_str(input, 0, vec![' ']);
+        let mut interner = Interner::new();
+        let lcs_object = LcsObject::from_str(input, 0, vec![' '], &mut interner);
+        assert_eq!(
+            lcs_object.display(&interner).to_string(),
+            "Command Failed on: node-127\n\t\t{0}"
+        );
+    }
+
+    #[test]
+    fn subsequence() {
+        let mut interner = Interner::new();
+        let object = object(&mut interner, &["Command", "*", "on:", "*"]);
+        let line = interner.lookup(&["Command", "Failed", "on:", "node-127"]);
+        assert!(object.is_subsequence_of(&line));
+        assert!(!object.is_subsequence_of(&line[1..]));
+        assert_eq!(object.get_lcs(&line), object.constants().count());
+    }
+
+    #[test]
+    fn extract_parameters() {
+        let mut interner = Interner::new();
+        let object = object(
+            &mut interner,
+            &["Jan", "22", "*", "combo", "*", "user", "*"],
+        );
+
+        let line: Vec<_> =
+            "Jan 22 04:16:40 combo su(pam_unix)[21719]: session opened for user news"
+                .split(' ')
+                .collect();
+        assert_eq!(
+            object.extract_parameters(&line, &interner),
+            vec![
+                vec!["04:16:40"],
+                vec!["su(pam_unix)[21719]:", "session", "opened", "for"],
+                vec!["news"],
+            ]
+        );
+
+        // Placeholders can stand for no token at all
+        let line: Vec<_> = "Jan 22 combo user".split(' ').collect();
+        assert_eq!(
+            object.extract_parameters(&line, &interner),
+            vec![Vec::<&str>::new(), vec![], vec![]]
+        );
+
+        // "extra" is not covered by a placeholder and "combo" is missing
+        let line: Vec<_> = "Jan 22 extra 04:16:40 user news".split(' ').collect();
+        assert_eq!(
+            object.extract_parameters(&line, &interner),
+            vec![vec!["extra", "04:16:40"], vec![], vec!["news"]]
+        );
+    }
+
+    #[test]
+    fn insert_with_dynamic_programming() {
+        let mut interner = Interner::new();
+        let line = |s: &'static str| -> Vec<&str> { s.split(' ').collect() };
+
+        let mut greedy = LcsObject::from_str_slice(&line("a b c d"), 0, &mut interner);
+        greedy.insert(&line("b c d a"), 1, &interner);
+
+        let mut optimal = LcsObject::from_str_slice(&line("a b c d"), 0, &mut interner);
+        let tokens = optimal.tokens.clone();
+        let previous = optimal.insert_with(
+            &line("b c d a"),
+            1,
+            &interner,
+            LcsAlgorithm::DynamicProgramming,
+        );
+        assert_eq!(previous, Some(tokens));
+        assert_eq!(optimal.lines_ids.to_vec(), vec![0, 1]);
+
+        let previous =
+            optimal.insert_with(&line("x b c d"), 2, &interner, LcsAlgorithm::HuntSzymanski);
+        assert_eq!(previous, None);
+
+        assert_eq!(greedy.template(&interner), vec!["*", "a"]);
+        assert_eq!(optimal.template(&interner), vec!["*", "b", "c", "d", "*"]);
+    }
+
+    #[test]
+    fn literal_stars_and_spaces_survive_merges() {
+        let mut interner = Interner::new();
+        let tokens = ["GET", "/api *", "*", "200"];
+        for &algorithm in &[LcsAlgorithm::Greedy, LcsAlgorithm::DynamicProgramming] {
+            let mut object = LcsObject::from_str_slice(&tokens, 0, &mut interner);
+            object.insert_with(&["GET", "/api *", "*", "404"], 1, &interner, algorithm);
+
+            assert_eq!(object.template(&interner), vec!["GET", "/api *", "*", "*"]);
+            assert!(!object.tokens[2].is_wildcard());
+            assert!(object.tokens[3].is_wildcard());
+            assert_eq!(
+                object.extract_parameters(&["GET", "/api *", "*", "500"], &interner),
+                vec![vec!["500"]]
+            );
+        }
+    }
+
+    #[test]
+    fn wildcards_are_typed() {
+        let mut interner = Interner::new();
+        let line = |s: &'static str| -> Vec<&str> { s.split(' ').collect() };
+
+        let mut object = LcsObject::from_str_slice(
+            &line("Temperature 41 exceeds threshold on 10.0.0.1"),
+            0,
+            &mut interner,
+        );
+        let previous = object.insert(
+            &line("Temperature 36.6 exceeds threshold on 10.0.0.2"),
+            1,
+            &interner,
+        );
+        assert!(previous.is_some());
+        assert_eq!(
+            object.param_types(),
+            vec![Some(ParamType::Float), Some(ParamType::Ip)]
+        );
+
+        // Types are updated even when the template doesn't change
+        let previous = object.insert(
+            &line("Temperature 40.1 exceeds threshold on node-1"),
+            2,
+            &interner,
+        );
+        assert!(previous.is_none());
+        assert_eq!(
+            object.typed_template(&interner),
+            vec![
+                "Temperature",
+                "<FLOAT>",
+                "exceeds",
+                "threshold",
+                "on",
+                "<TEXT>"
+            ]
+        );
+        assert_eq!(
+            format!("{:#}", object.display(&interner)),
+            "Temperature <FLOAT> exceeds threshold on <TEXT>\n\t\t{0, 1, 2}"
+        );
+        assert_eq!(
+            object.display(&interner).to_string(),
+            "Temperature * exceeds threshold on *\n\t\t{0, 1, 2}"
+        );
+    }
+
+    #[test]
+    fn wildcards_are_typed_from_the_alignment() {
+        let mut interner = Interner::new();
+        let line = |s: &'static str| -> Vec<&str> { s.split(' ').collect() };
+        let algorithm = LcsAlgorithm::DynamicProgramming;
+
+        let mut object =
+            LcsObject::from_str_slice(&line("read 12 bytes from disk"), 0, &mut interner);
+        object.insert_with(&line("read 13 bytes from 0x1f"), 1, &interner, algorithm);
+        assert_eq!(
+            object.param_types(),
+            vec![Some(ParamType::Int), Some(ParamType::Text)]
+        );
+
+        let previous =
+            object.insert_with(&line("read 14 bytes from 0x2f"), 2, &interner, algorithm);
+        assert!(previous.is_none());
+        assert_eq!(
+            object.param_types(),
+            vec![Some(ParamType::Int), Some(ParamType::Text)]
+        );
+
+        // Wildcards replacing several tokens of the previous template
+        let previous = object.insert_with(&line("read 15 16 from 0x3f"), 3, &interner, algorithm);
+        assert!(previous.is_some());
+        assert_eq!(object.template(&interner), vec!["read", "*", "from", "*"]);
+        assert_eq!(
+            object.param_types(),
+            vec![Some(ParamType::Text), Some(ParamType::Text)]
+        );
     }
 }
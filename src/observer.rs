@@ -0,0 +1,30 @@
+use crate::{interner::Interner, object::LcsObject, LcsTemplate, LineId};
+
+/// Callbacks notified by an `LcsMap` as lines are inserted.
+///
+/// All methods do nothing by default so implementors only need to override the
+/// events they are interested in. The tokens of the templates can be resolved
+/// with the map's `interner`. Observers are `Send + Sync` so that maps can be
+/// shared between threads.
+pub trait LcsObserver: Send + Sync {
+    /// A line did not match any template and a new one was created from it.
+    fn on_new_template(&mut self, _template: &LcsObject, _interner: &Interner) {}
+
+    /// A template was generalized to accommodate a new line. `old` are the
+    /// tokens of the template before the line was inserted.
+    fn on_template_updated(&mut self, _old: &LcsTemplate, _new: &LcsObject, _interner: &Interner) {}
+
+    /// A line matched an existing template. Called after
+    /// `on_template_updated()` when the template changed.
+    fn on_line_matched(&mut self, _template: &LcsObject, _line_id: LineId) {}
+}
+
+/// Observers registered on a map. They are not serialized nor compared.
+#[derive(Default)]
+pub(crate) struct Observers(pub Vec<Box<dyn LcsObserver>>);
+
+impl std::fmt::Debug for Observers {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Observers({})", self.0.len())
+    }
+}
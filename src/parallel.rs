@@ -0,0 +1,140 @@
+use rayon::prelude::*;
+
+use crate::{
+    map::{LcsMap, LineOffset},
+    tokenizer::Tokenizer,
+    TemplateId,
+};
+
+/// Number of lines learned by each partial map.
+///
+/// The partitions don't depend on the number of threads, so the templates are
+/// the same on every machine.
+const PARTITION_SIZE: usize = 4096;
+
+impl<T: Tokenizer + Clone + Send + Sync> LcsMap<T> {
+    /// Insert many lines, learning the templates of partitions of the lines
+    /// in parallel.
+    ///
+    /// The first partition is inserted in this map while each other partition
+    /// is learned in its own map, with the same settings as this one. These
+    /// partial maps are then merged, in order, into this map with `merge()`.
+    /// The templates can differ from the ones found by inserting the lines one
+    /// by one, but don't change from one run to the other. Observers are
+    /// notified of the templates created or generalized by the merges, not of
+    /// the lines of the partial maps.
+    ///
+    /// Returns the identifier of the template of each line.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use spell::map::LcsMap;
+    ///
+    /// let lines: Vec<_> = (0..10_000)
+    ///     .map(|i| format!("Command Failed on: node-{}", i))
+    ///     .collect();
+    ///
+    /// let mut map = LcsMap::new();
+    /// let ids = map.insert_batch(&lines);
+    /// assert_eq!(ids, vec![0; 10_000]);
+    /// assert_eq!(map.seq[0].template(&map.interner), vec!["Command", "Failed", "on:", "*"]);
+    /// ```
+    pub fn insert_batch<S: AsRef<str> + Sync>(&mut self, lines: &[S]) -> Vec<TemplateId> {
+        let (first, others) = lines.split_at(lines.len().min(PARTITION_SIZE));
+        let settings = (
+            self.tokenizer.clone(),
+            self.config.clone(),
+            self.log_format.clone(),
+            self.masking.clone(),
+        );
+
+        let (first, partials) = rayon::join(
+            || {
+                let indices: Vec<usize> = first
+                    .iter()
+                    .map(|line| self.insert_entry(line.as_ref()))
+                    .collect();
+                indices
+                    .into_iter()
+                    .map(|index| self.seq[index].id)
+                    .collect::<Vec<_>>()
+            },
+            || {
+                others
+                    .par_chunks(PARTITION_SIZE)
+                    .map(|partition| {
+                        let (tokenizer, config, log_format, masking) = settings.clone();
+                        let mut map = LcsMap::with_tokenizer(tokenizer);
+                        map.config = config;
+                        map.log_format = log_format;
+                        map.masking = masking;
+                        let indices: Vec<usize> = partition
+                            .iter()
+                            .map(|line| map.insert_entry(line.as_ref()))
+                            .collect();
+                        (map, indices)
+                    })
+                    .collect::<Vec<_>>()
+            },
+        );
+
+        let mut ids = first;
+        ids.reserve(others.len());
+        for (partial, indices) in partials {
+            let merged = self
+                .merge(partial, LineOffset::Next)
+                .expect("lines are numbered after the lines of the map");
+            ids.extend(indices.into_iter().map(|index| merged[index]));
+        }
+        ids
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::var_log_messages_lines;
+
+    #[test]
+    fn single_partition() {
+        let inputs = var_log_messages_lines();
+        let mut sequential = LcsMap::new();
+        inputs.iter().for_each(|input| sequential.insert(input));
+
+        let mut map = LcsMap::new();
+        assert_eq!(map.insert_batch(&inputs), vec![0, 1, 2, 2, 2, 3]);
+        assert_eq!(map, sequential);
+    }
+
+    #[test]
+    fn deterministic() {
+        let lines: Vec<String> = (0..3 * PARTITION_SIZE)
+            .map(|i| match i % 3 {
+                0 => format!("Command Failed on: node-{}", i),
+                1 => format!("Temperature ({}C) exceeds warning threshold", i % 90),
+                _ => format!("session opened for user {} by (uid={})", i % 7, i),
+            })
+            .collect();
+
+        let learn = || {
+            let mut map = LcsMap::new();
+            let ids = map.insert_batch(&lines[..PARTITION_SIZE + 10]);
+            let more = map.insert_batch(&lines[PARTITION_SIZE + 10..]);
+            (map, [ids, more].concat())
+        };
+        let (map, ids) = learn();
+        let (again, again_ids) = learn();
+        assert_eq!(again, map);
+        assert_eq!(again_ids, ids);
+
+        assert_eq!(map.line_id, lines.len());
+        assert_eq!(
+            map.seq.iter().map(|obj| obj.count()).sum::<usize>(),
+            lines.len()
+        );
+        for (line_id, id) in ids.into_iter().enumerate() {
+            assert!(map.get(id).unwrap().lines_ids.to_vec().contains(&line_id));
+        }
+    }
+}
@@ -0,0 +1,395 @@
+use std::io::{Read, Write};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    error::{Error, Result},
+    map::LcsMap,
+    tokenizer::Tokenizer,
+};
+
+/// Version of the layout of saved maps, bumped on incompatible changes.
+///
+/// Maps saved with an older version are converted when loaded.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// Start of the header line of saved maps, followed by the format and the
+/// schema version, for example `spell-map json 2`.
+const MAGIC: &str = "spell-map";
+
+/// Longest header accepted when loading.
+const MAX_HEADER_LENGTH: usize = 64;
+
+/// Encoding of a saved `LcsMap`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Human readable, and easy to inspect with other tools.
+    Json,
+    /// Compact, and faster to load.
+    Binary,
+}
+
+impl Format {
+    fn name(self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Binary => "binary",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "json" => Some(Format::Json),
+            "binary" => Some(Format::Binary),
+            _ => None,
+        }
+    }
+}
+
+impl<T: Tokenizer + Serialize> LcsMap<T> {
+    /// Write the map to `writer`, after a header line giving the format and
+    /// the schema version.
+    ///
+    /// The lookup structures and the observers are not saved.
+    pub fn save<W: Write>(&self, mut writer: W, format: Format) -> Result<()> {
+        writeln!(writer, "{} {} {}", MAGIC, format.name(), SCHEMA_VERSION)?;
+        match format {
+            Format::Json => serde_json::to_writer(&mut writer, self)?,
+            Format::Binary => bincode::serialize_into(&mut writer, self)?,
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+impl<T: Tokenizer + DeserializeOwned> LcsMap<T> {
+    /// Read a map written by `save()`, in any format.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use spell::{map::LcsMap, persistence::Format};
+    ///
+    /// let mut map = LcsMap::new();
+    /// map.insert("Command Failed on: node-127");
+    ///
+    /// let mut saved = Vec::new();
+    /// map.save(&mut saved, Format::Binary).unwrap();
+    /// let loaded = LcsMap::load(saved.as_slice()).unwrap();
+    /// assert_eq!(map, loaded);
+    /// ```
+    pub fn load<R: Read>(mut reader: R) -> Result<LcsMap<T>> {
+        let header = read_header(&mut reader)?;
+        let invalid = || Error::InvalidHeader(header.clone());
+
+        let mut fields = header.split(' ');
+        if fields.next() != Some(MAGIC) {
+            return Err(invalid());
+        }
+        let format = fields
+            .next()
+            .and_then(Format::from_name)
+            .ok_or_else(invalid)?;
+        let version: u32 = fields
+            .next()
+            .and_then(|version| version.parse().ok())
+            .ok_or_else(invalid)?;
+        if fields.next().is_some() {
+            return Err(invalid());
+        }
+        if version == 0 || version > SCHEMA_VERSION {
+            return Err(Error::UnsupportedVersion(version));
+        }
+
+        let map: LcsMap<T> = match version {
+            1 => deserialize::<v1::LcsMap<T>, R>(format, reader)?.into(),
+            _ => deserialize(format, reader)?,
+        };
+        map.check()?;
+        Ok(map)
+    }
+
+    /// Check that the templates only use interned tokens and track the values
+    /// of as many wildcards as they have, as the rest of the crate assumes.
+    fn check(&self) -> Result<()> {
+        for obj in &self.seq {
+            let invalid = |message| {
+                Err(Error::InvalidMap(format!(
+                    "template {} {}",
+                    obj.id, message
+                )))
+            };
+            if let Some(symbol) = obj
+                .constants()
+                .find(|&&symbol| !self.interner.contains(symbol))
+            {
+                return invalid(format!("has the unknown token {:?}", symbol));
+            }
+            let wildcards = obj
+                .tokens
+                .iter()
+                .filter(|token| token.is_wildcard())
+                .count();
+            match &obj.params {
+                Some(params) if params.wildcards.len() != wildcards => {
+                    return invalid(format!(
+                        "tracks the values of {} wildcards out of {}",
+                        params.wildcards.len(),
+                        wildcards
+                    ));
+                }
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn deserialize<D: DeserializeOwned, R: Read>(format: Format, reader: R) -> Result<D> {
+    Ok(match format {
+        Format::Json => serde_json::from_reader(reader)?,
+        Format::Binary => bincode::deserialize_from(reader)?,
+    })
+}
+
+/// Layout of the maps saved with the first version of the schema, before the
+/// values of the wildcards could be tracked.
+mod v1 {
+    use serde_derive::*;
+
+    use crate::{
+        config::{self, CandidateSelection, PruningBounds},
+        interner::Interner,
+        lcs::LcsAlgorithm,
+        line_ids::{LineIdStorage, LineIds},
+        log_format::LogFormat,
+        map,
+        masking::MaskingRule,
+        object,
+        tokenizer::Tokenizer,
+        LcsTemplate, LineId, TemplateId,
+    };
+
+    #[derive(Deserialize)]
+    pub struct LcsMap<T> {
+        seq: Vec<LcsObject>,
+        interner: Interner,
+        next_template_id: TemplateId,
+        line_id: LineId,
+        tokenizer: T,
+        #[serde(default)]
+        config: SpellConfig,
+        #[serde(default)]
+        log_format: Option<LogFormat>,
+        #[serde(default)]
+        masking: Vec<MaskingRule>,
+    }
+
+    #[derive(Deserialize)]
+    struct LcsObject {
+        id: TemplateId,
+        tokens: LcsTemplate,
+        lines_ids: LineIds,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(default)]
+    struct SpellConfig {
+        tau: f64,
+        pruning: Option<PruningBounds>,
+        candidates: CandidateSelection,
+        algorithm: LcsAlgorithm,
+        line_ids: LineIdStorage,
+    }
+
+    impl Default for SpellConfig {
+        fn default() -> SpellConfig {
+            let config = config::SpellConfig::default();
+            SpellConfig {
+                tau: config.tau,
+                pruning: config.pruning,
+                candidates: config.candidates,
+                algorithm: config.algorithm,
+                line_ids: config.line_ids,
+            }
+        }
+    }
+
+    impl<T: Tokenizer> From<LcsMap<T>> for map::LcsMap<T> {
+        fn from(saved: LcsMap<T>) -> map::LcsMap<T> {
+            let mut map = map::LcsMap::with_tokenizer(saved.tokenizer);
+            map.seq = saved
+                .seq
+                .into_iter()
+                .map(|obj| object::LcsObject {
+                    id: obj.id,
+                    tokens: obj.tokens,
+                    lines_ids: obj.lines_ids,
+                    params: None,
+                })
+                .collect();
+            map.interner = saved.interner;
+            map.next_template_id = saved.next_template_id;
+            map.line_id = saved.line_id;
+            map.config = config::SpellConfig {
+                tau: saved.config.tau,
+                pruning: saved.config.pruning,
+                candidates: saved.config.candidates,
+                algorithm: saved.config.algorithm,
+                line_ids: saved.config.line_ids,
+                tracked_values: None,
+            };
+            map.log_format = saved.log_format;
+            map.masking = saved.masking;
+            map
+        }
+    }
+}
+
+/// Read the header line, without consuming anything after it.
+fn read_header<R: Read>(reader: &mut R) -> Result<String> {
+    let mut header = Vec::new();
+    let mut byte = [0];
+    loop {
+        reader.read_exact(&mut byte)?;
+        if byte[0] == b'\n' {
+            break;
+        }
+        header.push(byte[0]);
+        if header.len() > MAX_HEADER_LENGTH {
+            break;
+        }
+    }
+    String::from_utf8(header)
+        .map_err(|e| Error::InvalidHeader(String::from_utf8_lossy(e.as_bytes()).into_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::SpellConfig, fixtures::var_log_messages_lines, line_ids::LineIdStorage,
+        log_format::LogFormat, masking::MaskingRule, tokenizer::KeepDelimiters,
+    };
+
+    #[test]
+    fn save_and_load() {
+        let lines = var_log_messages_lines();
+        for &format in &[Format::Json, Format::Binary] {
+            let mut map = LcsMap::with_config(
+                SpellConfig::default()
+                    .with_tau(0.4)
+                    .with_line_ids(LineIdStorage::Bitmap)
+                    .with_tracked_values(8),
+            );
+            map.log_format =
+                Some(LogFormat::new("<Month> <Day> <Time> <Host> <Component>: <Content>").unwrap());
+            map.masking = vec![MaskingRule::new(r"\d+", "<NUM>").unwrap()];
+            lines[..4].iter().for_each(|line| map.insert(line));
+
+            let mut saved = Vec::new();
+            map.save(&mut saved, format).unwrap();
+            let mut loaded: LcsMap = LcsMap::load(saved.as_slice()).unwrap();
+            assert_eq!(loaded, map);
+            assert_eq!(loaded.seq[0].tokens, map.seq[0].tokens);
+            assert!(map.seq[0].params.is_some());
+            assert!(map
+                .seq
+                .iter()
+                .any(|obj| obj.param_types().iter().any(Option::is_some)));
+            for (loaded, obj) in loaded.seq.iter().zip(&map.seq) {
+                assert_eq!(loaded.params, obj.params);
+            }
+            assert_eq!(loaded.to_string(), map.to_string());
+
+            // The loaded map keeps learning like the original one
+            lines[4..].iter().for_each(|line| {
+                map.insert(line);
+                loaded.insert(line);
+            });
+            assert_eq!(loaded, map);
+        }
+    }
+
+    #[test]
+    fn save_and_load_with_tokenizer() {
+        let mut map = LcsMap::with_tokenizer(KeepDelimiters::new(vec![' '], &["="]));
+        map.insert("session opened for uid=0");
+        map.insert("session opened for uid=42");
+
+        let mut saved = Vec::new();
+        map.save(&mut saved, Format::Json).unwrap();
+        assert!(saved.starts_with(b"spell-map json 2\n{"));
+        let loaded: LcsMap<KeepDelimiters> = LcsMap::load(saved.as_slice()).unwrap();
+        assert_eq!(loaded, map);
+    }
+
+    #[test]
+    fn load_version_1() {
+        let mut map = LcsMap::new();
+        var_log_messages_lines()
+            .iter()
+            .for_each(|line| map.insert(line));
+
+        let saved: &[u8] = include_bytes!("../fixtures/saved/map_v1.bin");
+        let loaded: LcsMap = LcsMap::load(saved).unwrap();
+        assert_eq!(loaded, map);
+        let saved: &[u8] = include_bytes!("../fixtures/saved/map_v1.json");
+        let loaded: LcsMap = LcsMap::load(saved).unwrap();
+        assert_eq!(loaded, map);
+    }
+
+    #[test]
+    fn invalid_headers() {
+        let load = |saved: &[u8]| LcsMap::<Vec<char>>::load(saved).unwrap_err();
+
+        assert!(matches!(load(b"{}"), Error::Io(_)));
+        assert!(matches!(
+            load(b"spell-map yaml 1\n"),
+            Error::InvalidHeader(_)
+        ));
+        assert!(matches!(load(b"spell-map json\n"), Error::InvalidHeader(_)));
+        assert!(matches!(load(b"other json 1\n"), Error::InvalidHeader(_)));
+        assert!(matches!(
+            load(format!("spell-map json {}\n{{}}", SCHEMA_VERSION + 1).as_bytes()),
+            Error::UnsupportedVersion(_)
+        ));
+        assert!(matches!(load(b"spell-map json 2\n{}"), Error::Json(_)));
+        assert!(matches!(load(b"spell-map binary 2\n"), Error::Binary(_)));
+    }
+
+    #[test]
+    fn inconsistent_maps() {
+        let mut map = LcsMap::with_config(SpellConfig::default().with_tracked_values(4));
+        map.insert("Command Failed on: node-127");
+        map.insert("Command Failed on: node-128");
+        let mut saved = Vec::new();
+        map.save(&mut saved, Format::Json).unwrap();
+        let header = b"spell-map json 2\n";
+        let json: serde_json::Value = serde_json::from_slice(&saved[header.len()..]).unwrap();
+
+        let load = |edit: &dyn Fn(&mut serde_json::Value)| {
+            let mut json = json.clone();
+            edit(&mut json);
+            let mut saved = header.to_vec();
+            serde_json::to_writer(&mut saved, &json).unwrap();
+            LcsMap::<Vec<char>>::load(saved.as_slice())
+        };
+        assert_eq!(load(&|_| ()).unwrap(), map);
+
+        let error = load(&|json| json["interner"].as_array_mut().unwrap().truncate(2)).unwrap_err();
+        assert!(matches!(error, Error::InvalidMap(_)));
+        let error = load(&|json| {
+            let wildcards = &mut json["seq"][0]["params"]["wildcards"];
+            wildcards
+                .as_array_mut()
+                .unwrap()
+                .push(serde_json::Value::Null);
+        })
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid saved map: template 0 tracks the values of 2 wildcards out of 1"
+        );
+    }
+}
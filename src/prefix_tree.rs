@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+
+use crate::LcsToken;
+
+/// Prefix tree over the constant tokens of the templates, used as the first
+/// lookup stage described in the Spell paper.
+///
+/// Templates are referenced by their index in `LcsMap::seq`. The tree is a
+/// cache derived from the templates; it is never serialized.
+#[derive(Clone)]
+pub(crate) struct PrefixTree {
+    nodes: Vec<Node>,
+    len: usize,
+}
+
+#[derive(Default, Clone)]
+struct Node {
+    children: HashMap<LcsToken, usize>,
+    templates: Vec<usize>,
+}
+
+impl PrefixTree {
+    pub fn new() -> PrefixTree {
+        PrefixTree {
+            nodes: vec![Node::default()],
+            len: 0,
+        }
+    }
+
+    /// Number of templates stored in the tree.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn insert<'a, I>(&mut self, index: usize, constants: I)
+    where
+        I: IntoIterator<Item = &'a LcsToken>,
+    {
+        let mut node = 0;
+        for token in constants {
+            node = match self.nodes[node].children.get(token) {
+                Some(&child) => child,
+                None => {
+                    let child = self.nodes.len();
+                    self.nodes.push(Node::default());
+                    self.nodes[node].children.insert(*token, child);
+                    child
+                }
+            };
+        }
+        self.nodes[node].templates.push(index);
+        self.len += 1;
+    }
+
+    pub fn remove<'a, I>(&mut self, index: usize, constants: I)
+    where
+        I: IntoIterator<Item = &'a LcsToken>,
+    {
+        let mut node = 0;
+        for token in constants {
+            match self.nodes[node].children.get(token) {
+                Some(&child) => node = child,
+                None => return,
+            }
+        }
+        let templates = &mut self.nodes[node].templates;
+        if let Some(position) = templates.iter().position(|&i| i == index) {
+            templates.remove(position);
+            self.len -= 1;
+        }
+    }
+
+    /// Walk the tree following the tokens of `tokenized` in order, returning
+    /// the templates stored on the path along with their number of constant
+    /// tokens. Every returned template has its constants appear, in order, in
+    /// `tokenized`.
+    pub fn lookup<'a>(
+        &'a self,
+        tokenized: &'a [LcsToken],
+    ) -> impl Iterator<Item = (usize, usize)> + 'a {
+        let mut path = vec![0];
+        let mut node = 0;
+        for token in tokenized {
+            if let Some(&child) = self.nodes[node].children.get(token) {
+                node = child;
+                path.push(node);
+            }
+        }
+        path.into_iter().enumerate().flat_map(move |(depth, node)| {
+            self.nodes[node]
+                .templates
+                .iter()
+                .map(move |&index| (index, depth))
+        })
+    }
+}
+
+impl Default for PrefixTree {
+    fn default() -> PrefixTree {
+        PrefixTree::new()
+    }
+}
+
+impl std::fmt::Debug for PrefixTree {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("PrefixTree")
+            .field("nodes", &self.nodes.len())
+            .field("templates", &self.len)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interner::Interner;
+
+    #[test]
+    fn lookup_follows_subsequence() {
+        let mut interner = Interner::new();
+        let mut seq =
+            |s: &str| -> Vec<LcsToken> { s.split(' ').map(|s| interner.intern(s)).collect() };
+        let mut tree = PrefixTree::new();
+        tree.insert(0, &seq("Command Failed on"));
+        tree.insert(1, &seq("Command Succeeded"));
+        tree.insert(2, &seq("Command"));
+        assert_eq!(tree.len(), 3);
+
+        let found: Vec<_> = tree.lookup(&seq("Command has Failed on node-1")).collect();
+        assert_eq!(found, vec![(2, 1), (0, 3)]);
+
+        let found: Vec<_> = tree.lookup(&seq("Nothing in common")).collect();
+        assert!(found.is_empty());
+    }
+
+    #[test]
+    fn remove() {
+        let mut interner = Interner::new();
+        let mut seq =
+            |s: &str| -> Vec<LcsToken> { s.split(' ').map(|s| interner.intern(s)).collect() };
+        let mut tree = PrefixTree::new();
+        tree.insert(0, &seq("a b c"));
+        tree.insert(1, &seq("a b"));
+        tree.remove(0, &seq("a b c"));
+        assert_eq!(tree.len(), 1);
+
+        let found: Vec<_> = tree.lookup(&seq("a b c")).collect();
+        assert_eq!(found, vec![(1, 2)]);
+
+        // Removing an unknown template is a no-op
+        tree.remove(0, &seq("x y"));
+        assert_eq!(tree.len(), 1);
+    }
+}
@@ -0,0 +1,466 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap, HashSet},
+};
+
+use serde_derive::*;
+
+use crate::{
+    interner::{Interner, Symbol},
+    line_ids::{LineIdStorage, LineIds},
+    map::LcsMap,
+    object::{parameter_spans, LcsObject},
+    template::TemplateToken,
+    tokenizer::Tokenizer,
+    LcsSeq, LcsToken, LineId, TemplateId,
+};
+
+/// Lines each value of a wildcard was found in, a value being the tokens
+/// absorbed by the wildcard.
+pub type Values = BTreeMap<Vec<String>, Vec<LineId>>;
+
+/// Distinct values absorbed by each wildcard of a template.
+///
+/// They are tracked when `SpellConfig::tracked_values` is set, and let
+/// `LcsMap::refine()` split the templates whose wildcards only ever took a
+/// few values.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ParamValues {
+    /// Most distinct values tracked for each wildcard.
+    pub limit: usize,
+    /// Values of each wildcard of the template, in order, or `None` once the
+    /// wildcard took more than `limit` values.
+    #[serde(with = "values_as_pairs")]
+    pub wildcards: Vec<Option<Values>>,
+}
+
+impl ParamValues {
+    pub fn new(limit: usize) -> ParamValues {
+        ParamValues {
+            limit,
+            wildcards: Vec::new(),
+        }
+    }
+
+    /// Whether the values of the templates are tracked by new templates with
+    /// this storage of their lines. All the lines of a template must be known.
+    pub fn is_supported(storage: LineIdStorage) -> bool {
+        match storage {
+            LineIdStorage::All | LineIdStorage::Bitmap => true,
+            LineIdStorage::Count | LineIdStorage::Recent(_) => false,
+        }
+    }
+
+    /// Record the values of a line inserted in a template.
+    ///
+    /// `template` is the template after the insertion, and `previous` the
+    /// template before the insertion if it changed. The values of the
+    /// `previous_lines` of the template are then carried over to the new
+    /// wildcards.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn insert<I>(
+        &mut self,
+        template: &[TemplateToken],
+        previous: Option<&[TemplateToken]>,
+        previous_lines: I,
+        symbols: &[LcsToken],
+        tokens: &[&str],
+        line_id: LineId,
+        interner: &Interner,
+    ) where
+        I: Iterator<Item = LineId>,
+    {
+        if let Some(previous) = previous {
+            self.remap(template, previous, previous_lines.collect(), interner);
+        }
+
+        let spans = parameter_spans(template, symbols, |_, _| false);
+        for (wildcard, span) in self.wildcards.iter_mut().zip(spans) {
+            let overflow = match wildcard {
+                Some(values) => {
+                    let value = tokens[span].iter().map(|s| s.to_string()).collect();
+                    values.entry(value).or_default().push(line_id);
+                    values.len() > self.limit
+                }
+                None => false,
+            };
+            if overflow {
+                *wildcard = None;
+            }
+        }
+    }
+
+    /// Carry the values of the wildcards of `previous` over to the ones of
+    /// `template`. A new wildcard standing for several tokens of `previous`
+    /// takes, for each line, the concatenation of their values.
+    fn remap(
+        &mut self,
+        template: &[TemplateToken],
+        previous: &[TemplateToken],
+        previous_lines: Vec<LineId>,
+        interner: &Interner,
+    ) {
+        let symbols: LcsSeq = previous
+            .iter()
+            .map(|s| s.constant().unwrap_or(Symbol::UNKNOWN))
+            .collect();
+        let mut ordinals = Vec::with_capacity(previous.len());
+        let mut count = 0;
+        for token in previous {
+            ordinals.push(if token.is_wildcard() {
+                count += 1;
+                Some(count - 1)
+            } else {
+                None
+            });
+        }
+
+        let mut old = std::mem::take(&mut self.wildcards);
+        old.resize(count, None);
+        let limit = self.limit;
+        self.wildcards = parameter_spans(template, &symbols, |_, _| false)
+            .into_iter()
+            .map(|span| {
+                if span.len() == 1 {
+                    if let Some(ordinal) = ordinals[span.start] {
+                        return old[ordinal].take();
+                    }
+                }
+
+                let mut lines: HashMap<LineId, Vec<String>> = previous_lines
+                    .iter()
+                    .map(|&line_id| (line_id, Vec::new()))
+                    .collect();
+                for i in span {
+                    match previous[i] {
+                        TemplateToken::Constant(symbol) => {
+                            let constant = interner.resolve(symbol);
+                            for value in lines.values_mut() {
+                                value.push(constant.to_string());
+                            }
+                        }
+                        TemplateToken::Wildcard(_) => {
+                            let values = old[ordinals[i]?].as_ref()?;
+                            for (value, line_ids) in values {
+                                for line_id in line_ids {
+                                    lines.get_mut(line_id)?.extend(value.iter().cloned());
+                                }
+                            }
+                        }
+                    }
+                }
+
+                let mut values = Values::new();
+                for (line_id, value) in lines {
+                    values.entry(value).or_default().push(line_id);
+                }
+                values.values_mut().for_each(|line_ids| line_ids.sort());
+                if values.len() > limit {
+                    None
+                } else {
+                    Some(values)
+                }
+            })
+            .collect();
+    }
+
+    /// Shift the identifiers of the lines by `offset`.
+    pub(crate) fn shift(&mut self, offset: LineId) {
+        let line_ids = self
+            .wildcards
+            .iter_mut()
+            .flatten()
+            .flat_map(|values| values.values_mut())
+            .flatten();
+        for line_id in line_ids {
+            *line_id += offset;
+        }
+    }
+}
+
+// Values are serialized as lists of `(value, lines)` pairs, as JSON only has
+// string keys.
+mod values_as_pairs {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::Values;
+    use crate::LineId;
+
+    type Pairs = Vec<Option<Vec<(Vec<String>, Vec<LineId>)>>>;
+
+    pub fn serialize<S: Serializer>(
+        wildcards: &[Option<Values>],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(wildcards.iter().map(|values| {
+            values
+                .as_ref()
+                .map(|values| values.iter().collect::<Vec<_>>())
+        }))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<Option<Values>>, D::Error> {
+        let pairs = Pairs::deserialize(deserializer)?;
+        Ok(pairs
+            .into_iter()
+            .map(|values| values.map(|values| values.into_iter().collect()))
+            .collect())
+    }
+}
+
+impl<T: Tokenizer> LcsMap<T> {
+    /// Split the templates having a wildcard that took at most `max_values`
+    /// distinct values, each of them in at least `min_lines` lines.
+    ///
+    /// Each value gets its own template, where the wildcard is replaced by the
+    /// tokens of the value. The most frequent value keeps the identifier of
+    /// the template. Only the templates whose values were tracked, as set by
+    /// `SpellConfig::tracked_values`, can be split. Returns the identifiers
+    /// of the templates created.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use spell::{config::SpellConfig, map::LcsMap};
+    ///
+    /// let mut map = LcsMap::with_config(SpellConfig::default().with_tracked_values(4));
+    /// map.insert("session opened for user root");
+    /// map.insert("session closed for user root");
+    /// map.insert("session opened for user news");
+    /// map.insert("session closed for user news");
+    /// assert_eq!(map.seq[0].template(&map.interner), vec!["session", "*", "for", "user", "*"]);
+    ///
+    /// assert_eq!(map.refine(2, 2), vec![1]);
+    /// assert_eq!(map.seq[0].template(&map.interner), vec!["session", "closed", "for", "user", "*"]);
+    /// assert_eq!(map.seq[1].template(&map.interner), vec!["session", "opened", "for", "user", "*"]);
+    /// assert_eq!(map.seq[1].lines_ids.to_vec(), vec![0, 2]);
+    /// ```
+    pub fn refine(&mut self, max_values: usize, min_lines: usize) -> Vec<TemplateId> {
+        let mut created = Vec::new();
+        let mut changed = false;
+        let mut index = 0;
+        while index < self.seq.len() {
+            match splittable_wildcard(&self.seq[index], max_values, min_lines) {
+                Some(wildcard) => {
+                    created.extend(self.split(index, wildcard));
+                    changed = true;
+                }
+                None => index += 1,
+            }
+        }
+        if changed {
+            self.rebuild_index();
+        }
+        created
+    }
+
+    /// Split the template at `index` on the values of its `wildcard`-th
+    /// wildcard, returning the identifiers of the templates created.
+    fn split(&mut self, index: usize, wildcard: usize) -> Vec<TemplateId> {
+        let obj = &self.seq[index];
+        let params = obj.params.as_ref().expect("values are tracked");
+        let position = obj
+            .tokens
+            .iter()
+            .enumerate()
+            .filter(|(_, token)| token.is_wildcard())
+            .nth(wildcard)
+            .map(|(position, _)| position)
+            .expect("wildcard exists");
+        let mut values: Vec<_> = params.wildcards[wildcard]
+            .clone()
+            .expect("values are tracked")
+            .into_iter()
+            .collect();
+        // Most frequent first, ties in the order of the values
+        values.sort_by_key(|(_, line_ids)| Reverse(line_ids.len()));
+
+        let mut split = Vec::with_capacity(values.len());
+        for (value, line_ids) in values {
+            let value: Vec<TemplateToken> = value
+                .iter()
+                .map(|s| TemplateToken::Constant(self.interner.intern(s)))
+                .collect();
+            let obj = &self.seq[index];
+            let mut tokens = obj.tokens[..position].to_vec();
+            tokens.extend(value);
+            tokens.extend_from_slice(&obj.tokens[position + 1..]);
+
+            let mut lines_ids = LineIds::new(obj.lines_ids.storage());
+            line_ids.iter().for_each(|&line_id| lines_ids.push(line_id));
+            let lines: HashSet<LineId> = line_ids.into_iter().collect();
+            let wildcards = params_of(obj)
+                .wildcards
+                .iter()
+                .enumerate()
+                .filter(|&(i, _)| i != wildcard)
+                .map(|(_, values)| {
+                    values.as_ref().map(|values| {
+                        values
+                            .iter()
+                            .filter_map(|(value, line_ids)| {
+                                let line_ids: Vec<LineId> = line_ids
+                                    .iter()
+                                    .cloned()
+                                    .filter(|line_id| lines.contains(line_id))
+                                    .collect();
+                                if line_ids.is_empty() {
+                                    None
+                                } else {
+                                    Some((value.clone(), line_ids))
+                                }
+                            })
+                            .collect()
+                    })
+                })
+                .collect();
+            split.push(LcsObject {
+                id: obj.id,
+                tokens,
+                lines_ids,
+                params: Some(ParamValues {
+                    limit: params_of(obj).limit,
+                    wildcards,
+                }),
+            });
+        }
+
+        let mut split = split.into_iter();
+        let first = split.next().expect("at least one value");
+        let previous = std::mem::replace(&mut self.seq[index], first);
+        for observer in &mut self.observers.0 {
+            observer.on_template_updated(&previous.tokens, &self.seq[index], &self.interner);
+        }
+        let mut created = Vec::new();
+        for obj in split {
+            let index = self.add_template(obj);
+            created.push(self.seq[index].id);
+        }
+        created
+    }
+}
+
+fn params_of(obj: &LcsObject) -> &ParamValues {
+    obj.params.as_ref().expect("values are tracked")
+}
+
+/// Wildcard of `obj` with the fewest values, if it can be split.
+fn splittable_wildcard(obj: &LcsObject, max_values: usize, min_lines: usize) -> Option<usize> {
+    let params = obj.params.as_ref()?;
+    params
+        .wildcards
+        .iter()
+        .enumerate()
+        .filter_map(|(i, values)| values.as_ref().map(|values| (i, values)))
+        .filter(|(_, values)| {
+            !values.is_empty()
+                && values.len() <= max_values
+                && values.values().all(|line_ids| line_ids.len() >= min_lines)
+                // Values must cover all the lines of the template
+                && values.values().map(Vec::len).sum::<usize>() == obj.count()
+        })
+        .min_by_key(|(_, values)| values.len())
+        .map(|(i, _)| i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::SpellConfig, fixtures::var_log_messages_lines, line_ids::LineIdStorage};
+
+    #[test]
+    fn values_follow_generalization() {
+        let mut map = LcsMap::with_config(SpellConfig::default().with_tracked_values(2));
+        let inputs = var_log_messages_lines();
+        inputs.iter().for_each(|input| map.insert(input));
+
+        // Values of the wildcards of `Jan 22 * combo * user *`
+        let values = |wildcard: usize| -> Option<Vec<(String, Vec<LineId>)>> {
+            map.seq[2].params.as_ref().unwrap().wildcards[wildcard]
+                .as_ref()
+                .map(|values| {
+                    values
+                        .iter()
+                        .map(|(value, line_ids)| (value.join(" "), line_ids.clone()))
+                        .collect()
+                })
+        };
+        // Too many distinct times
+        assert_eq!(values(0), None);
+        assert_eq!(values(1), None);
+        assert_eq!(
+            values(2),
+            Some(vec![
+                ("news".to_string(), vec![2, 3]),
+                ("unknown".to_string(), vec![4]),
+            ])
+        );
+
+        // Values are not tracked without all the identifiers of the lines
+        let mut map = LcsMap::with_config(
+            SpellConfig::default()
+                .with_tracked_values(2)
+                .with_line_ids(LineIdStorage::Count),
+        );
+        inputs.iter().for_each(|input| map.insert(input));
+        assert!(map.seq.iter().all(|obj| obj.params.is_none()));
+    }
+
+    #[test]
+    fn split_fixtures() {
+        let mut map = LcsMap::with_config(SpellConfig::default().with_tracked_values(8));
+        var_log_messages_lines()
+            .iter()
+            .for_each(|input| map.insert(input));
+        assert!(map.refine(2, 2).is_empty());
+
+        assert_eq!(map.refine(2, 1), vec![4, 5]);
+        let templates: Vec<_> = map
+            .seq
+            .iter()
+            .map(|obj| {
+                (
+                    obj.id,
+                    obj.template(&map.interner).join(" "),
+                    obj.lines_ids.to_vec(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            templates[2..],
+            [
+                (
+                    2,
+                    "Jan 22 04:16:40 combo su(pam_unix)[21719]: session opened for user news"
+                        .to_string(),
+                    vec![2]
+                ),
+                (
+                    3,
+                    "Jan 22 05:23:19 combo sshd(pam_unix)[24892]: authentication failure; logname= uid=0 euid=0 tty=NODEVssh ruser= rhost=server3.sugolan.hu"
+                        .to_string(),
+                    vec![5]
+                ),
+                (
+                    4,
+                    "Jan 22 05:23:19 combo sshd(pam_unix)[24892]: check pass; user unknown"
+                        .to_string(),
+                    vec![4]
+                ),
+                (
+                    5,
+                    "Jan 22 04:16:41 combo su(pam_unix)[21719]: session closed for user news"
+                        .to_string(),
+                    vec![3]
+                ),
+            ]
+        );
+        assert_eq!(map.next_template_id, 6);
+
+        // Lines now match the refined templates
+        map.insert("Jan 22 04:17:12 combo su(pam_unix)[21719]: session closed for user news");
+        assert_eq!(map.seq[5].lines_ids.to_vec(), vec![3, 6]);
+    }
+}
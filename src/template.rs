@@ -0,0 +1,316 @@
+use serde_derive::*;
+
+use crate::{interner::Interner, LcsToken};
+
+/// Token of a template: either a constant, or a wildcard standing for the
+/// variable part of the lines.
+///
+/// Wildcards are distinct from the tokens of the lines, so a literal `*` in a
+/// line is a constant like any other token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TemplateToken {
+    Constant(LcsToken),
+    /// Wildcard, along with the kinds of values it absorbed so far.
+    Wildcard(ParamKinds),
+}
+
+impl TemplateToken {
+    /// Wildcard that has not absorbed any value yet.
+    pub fn wildcard() -> TemplateToken {
+        TemplateToken::Wildcard(ParamKinds::default())
+    }
+
+    /// Symbol of a constant.
+    pub fn constant(&self) -> Option<LcsToken> {
+        match *self {
+            TemplateToken::Constant(symbol) => Some(symbol),
+            TemplateToken::Wildcard(_) => None,
+        }
+    }
+
+    pub fn is_wildcard(&self) -> bool {
+        matches!(self, TemplateToken::Wildcard(_))
+    }
+
+    /// Type of the values of a wildcard, if it absorbed any.
+    pub fn param_type(&self) -> Option<ParamType> {
+        match *self {
+            TemplateToken::Constant(_) => None,
+            TemplateToken::Wildcard(kinds) => kinds.param_type(),
+        }
+    }
+
+    /// Kinds of the values the token stands for.
+    pub fn kinds(&self, interner: &Interner) -> ParamKinds {
+        match *self {
+            TemplateToken::Constant(symbol) => ParamType::of(interner.resolve(symbol)).into(),
+            TemplateToken::Wildcard(kinds) => kinds,
+        }
+    }
+
+    /// String of the token, `*` for wildcards.
+    pub fn resolve<'a>(&self, interner: &'a Interner) -> &'a str {
+        match *self {
+            TemplateToken::Constant(symbol) => interner.resolve(symbol),
+            TemplateToken::Wildcard(_) => "*",
+        }
+    }
+
+    /// Same as `resolve()`, but wildcards are shown with their type, like
+    /// `<FLOAT>`. Wildcards without a type are still shown as `*`.
+    pub fn resolve_typed<'a>(&self, interner: &'a Interner) -> &'a str {
+        match self.param_type() {
+            Some(param_type) => param_type.placeholder(),
+            None => self.resolve(interner),
+        }
+    }
+}
+
+/// Constant tokens of a template, in order.
+pub fn constants(template: &[TemplateToken]) -> impl Iterator<Item = &LcsToken> + Clone {
+    template.iter().filter_map(|token| match token {
+        TemplateToken::Constant(symbol) => Some(symbol),
+        TemplateToken::Wildcard(_) => None,
+    })
+}
+
+/// Whether two templates have the same constants and wildcards, regardless of
+/// the kinds of values absorbed by the wildcards.
+pub fn same_shape(left: &[TemplateToken], right: &[TemplateToken]) -> bool {
+    left.len() == right.len()
+        && left
+            .iter()
+            .zip(right)
+            .all(|(l, r)| l.constant() == r.constant())
+}
+
+impl From<LcsToken> for TemplateToken {
+    fn from(symbol: LcsToken) -> TemplateToken {
+        TemplateToken::Constant(symbol)
+    }
+}
+
+/// Type of a value found in place of a wildcard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ParamType {
+    Int,
+    Float,
+    Ip,
+    Hex,
+    Path,
+    /// Anything else, including values spanning several tokens.
+    Text,
+}
+
+impl ParamType {
+    const ALL: [ParamType; 6] = [
+        ParamType::Int,
+        ParamType::Float,
+        ParamType::Ip,
+        ParamType::Hex,
+        ParamType::Path,
+        ParamType::Text,
+    ];
+
+    /// Type of a single token.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use spell::template::ParamType;
+    ///
+    /// assert_eq!(ParamType::of("-42"), ParamType::Int);
+    /// assert_eq!(ParamType::of("36.6"), ParamType::Float);
+    /// assert_eq!(ParamType::of("192.168.0.1"), ParamType::Ip);
+    /// assert_eq!(ParamType::of("0x7f3a"), ParamType::Hex);
+    /// assert_eq!(ParamType::of("/var/log/messages"), ParamType::Path);
+    /// assert_eq!(ParamType::of("news"), ParamType::Text);
+    /// ```
+    pub fn of(value: &str) -> ParamType {
+        let digits = value.trim_start_matches(['-', '+']);
+        if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) {
+            ParamType::Int
+        } else if digits.starts_with(|c: char| c.is_ascii_digit())
+            && digits
+                .bytes()
+                .all(|b| b.is_ascii_digit() || b"eE.+-".contains(&b))
+            && value.parse::<f64>().is_ok()
+        {
+            ParamType::Float
+        } else if value.parse::<std::net::IpAddr>().is_ok()
+            || value.parse::<std::net::SocketAddr>().is_ok()
+        {
+            ParamType::Ip
+        } else if is_hex(value) {
+            ParamType::Hex
+        } else if is_path(value) {
+            ParamType::Path
+        } else {
+            ParamType::Text
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ParamType::Int => "INT",
+            ParamType::Float => "FLOAT",
+            ParamType::Ip => "IP",
+            ParamType::Hex => "HEX",
+            ParamType::Path => "PATH",
+            ParamType::Text => "TEXT",
+        }
+    }
+
+    /// How a wildcard of this type is shown in a template.
+    pub fn placeholder(self) -> &'static str {
+        match self {
+            ParamType::Int => "<INT>",
+            ParamType::Float => "<FLOAT>",
+            ParamType::Ip => "<IP>",
+            ParamType::Hex => "<HEX>",
+            ParamType::Path => "<PATH>",
+            ParamType::Text => "<TEXT>",
+        }
+    }
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+fn is_hex(value: &str) -> bool {
+    let (digits, prefixed) = match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(digits) => (digits, true),
+        None => (value, false),
+    };
+    !digits.is_empty()
+        && digits.bytes().all(|b| b.is_ascii_hexdigit())
+        // Without a prefix, only long enough values mixing in letters
+        && (prefixed || (digits.len() >= 8 && digits.bytes().any(|b| b.is_ascii_alphabetic())))
+}
+
+fn is_path(value: &str) -> bool {
+    value.len() > 1
+        && ["/", "./", "../", "~/"]
+            .iter()
+            .any(|prefix| value.starts_with(prefix))
+}
+
+/// Set of the types of the values absorbed by a wildcard.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ParamKinds(u8);
+
+impl ParamKinds {
+    pub fn insert(&mut self, param_type: ParamType) {
+        self.0 |= param_type.bit();
+    }
+
+    /// Add the types of `other` to this set.
+    pub fn extend(&mut self, other: ParamKinds) {
+        self.0 |= other.0;
+    }
+
+    pub fn contains(self, param_type: ParamType) -> bool {
+        self.0 & param_type.bit() != 0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = ParamType> {
+        ParamType::ALL
+            .iter()
+            .cloned()
+            .filter(move |&param_type| self.contains(param_type))
+    }
+
+    /// Most specific type covering all the values: integers mixed with floats
+    /// are floats, with hexadecimal values are hexadecimal, and any other mix
+    /// is text.
+    pub fn param_type(self) -> Option<ParamType> {
+        let mut types = self.iter().filter(|&t| t != ParamType::Int);
+        match (types.next(), types.next()) {
+            (None, _) if self.is_empty() => None,
+            (None, _) => Some(ParamType::Int),
+            (Some(param_type), None)
+                if param_type == ParamType::Float
+                    || param_type == ParamType::Hex
+                    || !self.contains(ParamType::Int) =>
+            {
+                Some(param_type)
+            }
+            _ => Some(ParamType::Text),
+        }
+    }
+}
+
+impl From<ParamType> for ParamKinds {
+    fn from(param_type: ParamType) -> ParamKinds {
+        let mut kinds = ParamKinds::default();
+        kinds.insert(param_type);
+        kinds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn param_types() {
+        for &(value, expected) in &[
+            ("0", ParamType::Int),
+            ("1e-3", ParamType::Float),
+            ("-0.5", ParamType::Float),
+            ("inf", ParamType::Text),
+            ("1.4.1", ParamType::Text),
+            ("10.0.0.1:22", ParamType::Ip),
+            ("::1", ParamType::Ip),
+            ("deadbeef", ParamType::Hex),
+            ("cafe", ParamType::Text),
+            ("./run.sh", ParamType::Path),
+            ("/", ParamType::Text),
+            ("(uid=0)", ParamType::Text),
+        ] {
+            assert_eq!(ParamType::of(value), expected, "{}", value);
+        }
+    }
+
+    #[test]
+    fn kinds_are_joined() {
+        let kinds = |types: &[ParamType]| {
+            let mut kinds = ParamKinds::default();
+            types.iter().for_each(|&t| kinds.insert(t));
+            kinds
+        };
+        assert_eq!(kinds(&[]).param_type(), None);
+        assert_eq!(kinds(&[ParamType::Int]).param_type(), Some(ParamType::Int));
+        assert_eq!(kinds(&[ParamType::Ip]).param_type(), Some(ParamType::Ip));
+        assert_eq!(
+            kinds(&[ParamType::Int, ParamType::Float]).param_type(),
+            Some(ParamType::Float)
+        );
+        assert_eq!(
+            kinds(&[ParamType::Int, ParamType::Hex]).param_type(),
+            Some(ParamType::Hex)
+        );
+        assert_eq!(
+            kinds(&[ParamType::Int, ParamType::Path]).param_type(),
+            Some(ParamType::Text)
+        );
+        assert_eq!(
+            kinds(&[ParamType::Float, ParamType::Ip]).param_type(),
+            Some(ParamType::Text)
+        );
+        assert_eq!(
+            kinds(&[ParamType::Float, ParamType::Ip])
+                .iter()
+                .collect::<Vec<_>>(),
+            vec![ParamType::Float, ParamType::Ip]
+        );
+    }
+}
@@ -0,0 +1,240 @@
+use std::convert::TryFrom;
+
+use regex::Regex;
+use serde_derive::*;
+
+use crate::{
+    error::{Error, Result},
+    tokenize, LcsDelimiters,
+};
+
+/// Splits a line into the tokens fed to Spell.
+///
+/// Tokens are slices of the input. Leading and trailing whitespace is trimmed
+/// and empty tokens are dropped.
+pub trait Tokenizer {
+    fn tokenize<'a>(&self, input: &'a str) -> Vec<&'a str>;
+}
+
+/// Splits on any of the characters.
+impl Tokenizer for LcsDelimiters {
+    fn tokenize<'a>(&self, input: &'a str) -> Vec<&'a str> {
+        tokenize(input, self).collect()
+    }
+}
+
+/// Splits on any of the strings, the longest one winning when several match.
+///
+/// # Examples
+///
+/// ```
+/// use spell::tokenizer::{StringDelimiters, Tokenizer};
+///
+/// let tokenizer = StringDelimiters::new(&[", ", " => ", " "]);
+/// assert_eq!(tokenizer.tokenize("a, b => c d"), vec!["a", "b", "c", "d"]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StringDelimiters {
+    pub delimiters: Vec<String>,
+}
+
+impl StringDelimiters {
+    pub fn new(delimiters: &[&str]) -> StringDelimiters {
+        StringDelimiters {
+            delimiters: delimiters.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+}
+
+impl Tokenizer for StringDelimiters {
+    fn tokenize<'a>(&self, input: &'a str) -> Vec<&'a str> {
+        split(input, |rest| {
+            longest_prefix(rest, self.delimiters.iter().map(|s| s.as_str())).map(|len| (len, false))
+        })
+    }
+}
+
+/// Splits on the matches of a regular expression.
+///
+/// # Examples
+///
+/// ```
+/// use spell::tokenizer::{RegexDelimiters, Tokenizer};
+///
+/// let tokenizer = RegexDelimiters::new(r"[\s,;]+").unwrap();
+/// assert_eq!(tokenizer.tokenize("a, b;c"), vec!["a", "b", "c"]);
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct RegexDelimiters {
+    regex: Regex,
+}
+
+impl RegexDelimiters {
+    pub fn new(pattern: &str) -> Result<RegexDelimiters> {
+        Ok(RegexDelimiters {
+            regex: Regex::new(pattern)?,
+        })
+    }
+
+    pub fn pattern(&self) -> &str {
+        self.regex.as_str()
+    }
+}
+
+impl Tokenizer for RegexDelimiters {
+    fn tokenize<'a>(&self, input: &'a str) -> Vec<&'a str> {
+        self.regex
+            .split(input.trim())
+            .filter(|s| !s.is_empty())
+            .collect()
+    }
+}
+
+impl PartialEq for RegexDelimiters {
+    fn eq(&self, other: &RegexDelimiters) -> bool {
+        self.pattern() == other.pattern()
+    }
+}
+
+impl TryFrom<String> for RegexDelimiters {
+    type Error = Error;
+
+    fn try_from(pattern: String) -> Result<RegexDelimiters> {
+        RegexDelimiters::new(&pattern)
+    }
+}
+
+impl From<RegexDelimiters> for String {
+    fn from(tokenizer: RegexDelimiters) -> String {
+        tokenizer.regex.as_str().to_string()
+    }
+}
+
+/// Splits on the `delimiters` characters, which are dropped, and around the
+/// `kept` strings, which become tokens of their own.
+///
+/// # Examples
+///
+/// ```
+/// use spell::tokenizer::{KeepDelimiters, Tokenizer};
+///
+/// let tokenizer = KeepDelimiters::new(vec![' '], &["=", "=>"]);
+/// assert_eq!(
+///     tokenizer.tokenize("uid=0 a=>b"),
+///     vec!["uid", "=", "0", "a", "=>", "b"]
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeepDelimiters {
+    pub delimiters: LcsDelimiters,
+    pub kept: Vec<String>,
+}
+
+impl KeepDelimiters {
+    pub fn new(delimiters: LcsDelimiters, kept: &[&str]) -> KeepDelimiters {
+        KeepDelimiters {
+            delimiters,
+            kept: kept.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+}
+
+impl Tokenizer for KeepDelimiters {
+    fn tokenize<'a>(&self, input: &'a str) -> Vec<&'a str> {
+        split(input, |rest| {
+            if let Some(len) = longest_prefix(rest, self.kept.iter().map(|s| s.as_str())) {
+                Some((len, true))
+            } else {
+                let c = rest.chars().next()?;
+                if self.delimiters.contains(&c) {
+                    Some((c.len_utf8(), false))
+                } else {
+                    None
+                }
+            }
+        })
+    }
+}
+
+fn longest_prefix<'a, I>(input: &str, prefixes: I) -> Option<usize>
+where
+    I: Iterator<Item = &'a str>,
+{
+    prefixes
+        .filter(|prefix| !prefix.is_empty() && input.starts_with(prefix))
+        .map(|prefix| prefix.len())
+        .max()
+}
+
+/// Split `input` on the delimiters found by `delimiter_at`, which returns the
+/// length of the delimiter starting the given string, if any, and whether to
+/// keep it as a token.
+fn split<F>(input: &str, delimiter_at: F) -> Vec<&str>
+where
+    F: Fn(&str) -> Option<(usize, bool)>,
+{
+    let input = input.trim();
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut position = 0;
+    while position < input.len() {
+        match delimiter_at(&input[position..]) {
+            Some((len, keep)) => {
+                tokens.push(&input[start..position]);
+                if keep {
+                    tokens.push(&input[position..position + len]);
+                }
+                position += len;
+                start = position;
+            }
+            None => {
+                position += input[position..].chars().next().map_or(1, |c| c.len_utf8());
+            }
+        }
+    }
+    tokens.push(&input[start..]);
+    tokens.retain(|s| !s.is_empty());
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn char_delimiters() {
+        let tokenizer: LcsDelimiters = vec![' ', ','];
+        assert_eq!(
+            tokenizer.tokenize(" Command Failed on: node-127,node-234 "),
+            vec!["Command", "Failed", "on:", "node-127", "node-234"]
+        );
+    }
+
+    #[test]
+    fn string_delimiters() {
+        let tokenizer = StringDelimiters::new(&[", ", "=>"]);
+        assert_eq!(tokenizer.tokenize("a, b,c=>d=>"), vec!["a", "b,c", "d"]);
+        assert_eq!(StringDelimiters::new(&[]).tokenize("a b"), vec!["a b"]);
+    }
+
+    #[test]
+    fn regex_delimiters() {
+        let tokenizer = RegexDelimiters::new(r"\s*=>\s*|\s+").unwrap();
+        assert_eq!(
+            tokenizer.tokenize("key => value other"),
+            vec!["key", "value", "other"]
+        );
+        assert!(RegexDelimiters::new("(").is_err());
+    }
+
+    #[test]
+    fn keep_delimiters() {
+        let tokenizer = KeepDelimiters::new(vec![' '], &["="]);
+        assert_eq!(
+            tokenizer.tokenize("logname= uid=0 euid=0"),
+            vec!["logname", "=", "uid", "=", "0", "euid", "=", "0"]
+        );
+        assert_eq!(tokenizer.tokenize("é=ü"), vec!["é", "=", "ü"]);
+    }
+}